@@ -1,95 +1,239 @@
-use crate::audio::AudioPlayer;
-use crate::config::Config;
-use crate::download;
+// Without the daemon `Backend` only has the local variant, so matching it can't fail
+#![cfg_attr(not(unix), allow(irrefutable_let_patterns))]
+
+use crate::audio::{self, AudioPlayer, SkippedChannel};
+use crate::channels::{self, ChannelDef, ChannelRegistry};
+use crate::config::{Config, Dirs, SleepAction, SoundConfig};
+use crate::control::{self, ChannelStatus, Command, SleepStatus, Status};
+#[cfg(unix)]
+use crate::daemon;
+use crate::download::{self, DownloadReporter};
+use crate::filter;
+#[cfg(target_os = "linux")]
+use crate::mpris::MprisServer;
 use crate::prompt;
+use crate::sleep::{self, SleepTimer};
+use crate::soundpack::{self, PackManifest, UpdateCheck};
 use crate::ui;
-use anyhow::Result;
+use anyhow::{Context, Result};
 use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
 use ratatui::DefaultTerminal;
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// How much the volume keys change the volume by
+const VOLUME_STEP: i16 = 5;
+
+/// How much the pan keys move the stereo position by
+const PAN_STEP: i16 = 10;
+
+/// Ramp between the volume steps of the sleep timer fade-out, matching the main loop interval
+const SLEEP_FADE_RAMP: Duration = Duration::from_millis(100);
 
-// GitHub repository information for downloading sounds
-const GITHUB_USER: &str = "ebithril";
-const GITHUB_REPO: &str = "relax-player";
+/// Question whether to download sounds
+const DOWNLOAD_PROMPT: prompt::PromptType = prompt::PromptType::YesNo {
+    yes: "download",
+    no: "skip",
+};
 
+/// A column in the mixer: either a registered sound channel or the master volume
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Channel {
-    Rain,
-    Thunder,
-    Campfire,
+    /// Index into the channel registry
+    Sound(usize),
     Master,
 }
 
-impl Channel {
-    /// Get the name of this channel for display
-    pub fn name(&self) -> &str {
-        match self {
-            Channel::Rain => "Rain",
-            Channel::Thunder => "Thunder",
-            Channel::Campfire => "Campfire",
-            Channel::Master => "Master",
-        }
-    }
-
-    /// Get all channels in order
-    pub fn all() -> [Channel; 4] {
-        [
-            Channel::Rain,
-            Channel::Thunder,
-            Channel::Campfire,
-            Channel::Master,
-        ]
-    }
+/// Settings that only apply to a single run of the player and are never saved
+#[derive(Debug, Default)]
+pub struct Session {
+    /// Channel volume overrides as (channel, volume)
+    pub volumes: Vec<(String, u8)>,
+    /// Channels to mute
+    pub muted: Vec<String>,
+    pub master: Option<u8>,
+    /// Preset to start with, applied before the other overrides
+    pub preset: Option<String>,
+    /// Output device to play on instead of the one in the config
+    pub device: Option<String>,
+    /// Stop playing after this long
+    pub duration: Option<Duration>,
+}
 
-    /// Move to the next channel (right)
-    pub fn next(&self) -> Self {
-        match self {
-            Channel::Rain => Channel::Thunder,
-            Channel::Thunder => Channel::Campfire,
-            Channel::Campfire => Channel::Master,
-            Channel::Master => Channel::Master,
-        }
+impl Session {
+    /// Check if the session changes any settings that would otherwise be saved
+    pub fn overrides_config(&self) -> bool {
+        !self.volumes.is_empty() || !self.muted.is_empty() || self.master.is_some()
     }
+}
 
-    /// Move to the previous channel (left)
-    pub fn prev(&self) -> Self {
-        match self {
-            Channel::Rain => Channel::Rain,
-            Channel::Thunder => Channel::Rain,
-            Channel::Campfire => Channel::Thunder,
-            Channel::Master => Channel::Campfire,
-        }
-    }
+/// Where the sound is played
+pub enum Backend {
+    /// This process owns the audio output
+    Local(AudioPlayer),
+    /// A daemon plays the sound, every change is sent to it over the control socket
+    #[cfg(unix)]
+    Remote(daemon::Client),
 }
 
 pub struct App {
-    pub audio: AudioPlayer,
+    pub backend: Backend,
     pub config: Config,
+    pub registry: ChannelRegistry,
     pub selected_channel: Channel,
+    pub sleep_timer: Option<SleepTimer>,
+    pub paused: bool,
+    /// The last preset that was loaded or saved
+    pub preset: Option<String>,
+    /// Output device in use, `None` for the system default
+    pub device: Option<String>,
     pub should_quit: bool,
+    session: Session,
+    /// What to do when the sleep timer runs out
+    sleep_action: SleepAction,
+    /// Whether changes are written to the config file
+    persist_config: bool,
+    /// Where the config file and sounds are
+    dirs: Dirs,
+    /// Problem with the output device to tell the user about once playback starts
+    device_notice: Option<String>,
+    /// Sounds that couldn't be loaded, to tell the user about once playback started
+    sound_notice: Option<String>,
+    /// When the audio was last rendered, offline output only advances when rendered
+    last_render: Instant,
+    /// Desktop media controls, if the session bus is available
+    #[cfg(target_os = "linux")]
+    mpris: Option<MprisServer>,
 }
 
 impl App {
-    pub fn new() -> Result<Self> {
-        let config = Config::load()?;
-        let audio = AudioPlayer::new()?;
+    pub fn new(session: Session, dirs: Dirs) -> Result<Self> {
+        let config = Config::load(&dirs.config_file)?;
+        let device = session.device.clone().or_else(|| config.device.clone());
+
+        // A missing device shouldn't keep the player from starting: an unplugged device falls
+        // back to the default one, and without any sound card nothing is heard but everything
+        // else works (e.g. a daemon on a headless machine)
+        let no_output = |error: anyhow::Error| {
+            let notice = format!(
+                "{:#}. No audio output is available, nothing will be heard.",
+                error
+            );
+            (AudioPlayer::null(), None, Some(notice))
+        };
+        let (audio, device, device_notice) = match AudioPlayer::new(device.as_deref()) {
+            Ok(audio) => (audio, device, None),
+            Err(error) if device.is_some() => match AudioPlayer::new(None) {
+                Ok(audio) => {
+                    let notice =
+                        format!("{:#}. Playing on the default output device instead.", error);
+                    (audio, None, Some(notice))
+                }
+                Err(_) => no_output(error),
+            },
+            Err(error) => no_output(error),
+        };
+
+        let persist_config = !session.overrides_config();
+        let mut app =
+            Self::with_backend(Backend::Local(audio), dirs, config, session, persist_config)?;
+        app.device = device;
+        app.device_notice = device_notice;
+
+        Ok(app)
+    }
+
+    /// Create a TUI that controls a running daemon instead of playing sound itself
+    #[cfg(unix)]
+    pub fn connect(client: daemon::Client, dirs: Dirs) -> Result<Self> {
+        let config = Config::load(&dirs.config_file)?;
+        // The daemon owns the config file
+        let mut app = Self::with_backend(
+            Backend::Remote(client),
+            dirs,
+            config,
+            Session::default(),
+            false,
+        )?;
+        app.registry.retain_available(&app.dirs.sounds);
+        app.dispatch(Command::Status)?;
+
+        Ok(app)
+    }
+
+    /// Create the app around its audio backend. The config file is only written to if
+    /// `persist_config` is set.
+    fn with_backend(
+        backend: Backend,
+        dirs: Dirs,
+        mut config: Config,
+        session: Session,
+        persist_config: bool,
+    ) -> Result<Self> {
+        let packs = soundpack::installed_packs(&dirs.sounds)?;
+        let mut registry = ChannelRegistry::from_packs(&packs);
+        for channel in channels::custom_channels(&dirs.custom_sounds())? {
+            registry.register(channel);
+        }
+        if config.add_missing_channels(&registry) && persist_config {
+            config.save(&dirs.config_file)?;
+        }
+
+        let sleep_action = config.sleep.action;
+        let selected_channel = if registry.is_empty() {
+            Channel::Master
+        } else {
+            Channel::Sound(0)
+        };
 
         Ok(Self {
-            audio,
+            backend,
             config,
-            selected_channel: Channel::Rain,
+            registry,
+            selected_channel,
+            sleep_timer: None,
+            paused: false,
+            preset: None,
+            device: None,
             should_quit: false,
+            sleep_action,
+            persist_config,
+            dirs,
+            device_notice: None,
+            sound_notice: None,
+            last_render: Instant::now(),
+            session,
+            #[cfg(target_os = "linux")]
+            mpris: None,
         })
     }
 
     pub fn run(&mut self) -> Result<()> {
         let mut terminal = ratatui::init();
 
-        self.handle_sounds(&mut terminal)?;
+        let result = self.run_tui(&mut terminal);
 
-        // Set initial volumes
-        self.update_audio_volumes();
+        ratatui::restore();
 
-        self.audio.load_sounds_and_play()?;
+        result
+    }
+
+    fn run_tui(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        if matches!(self.backend, Backend::Local(_)) {
+            if let Some(notice) = self.device_notice.take() {
+                prompt::run_prompt(
+                    terminal,
+                    "Output Device",
+                    &notice,
+                    prompt::PromptType::Error,
+                )?;
+            }
+            self.handle_sounds(terminal)?;
+            self.start_playback()?;
+            if let Some(notice) = self.sound_notice.take() {
+                prompt::run_prompt(terminal, "Sounds", &notice, prompt::PromptType::Error)?;
+            }
+        }
 
         terminal.clear()?;
 
@@ -101,23 +245,291 @@ impl App {
             if event::poll(std::time::Duration::from_millis(100))? {
                 if let Event::Key(key) = event::read()? {
                     if key.kind == KeyEventKind::Press {
-                        self.handle_key_event(key)?;
+                        self.handle_key_event(key, terminal)?;
                     }
                 }
             }
 
+            self.tick()?;
+
             // Check if we should quit
             if self.should_quit {
                 break;
             }
         }
 
-        ratatui::restore();
+        Ok(())
+    }
+
+    /// Play the mix without the terminal UI until stopped or the session duration runs out
+    pub fn run_headless(&mut self) -> Result<()> {
+        // There is no way to resume without the UI, so the timer always ends the session
+        self.sleep_action = SleepAction::Quit;
+
+        if let Some(notice) = self.device_notice.take() {
+            eprintln!("{}", notice);
+        }
+        self.handle_sounds_headless()?;
+        self.start_playback()?;
+        if let Some(notice) = self.sound_notice.take() {
+            eprintln!("{}", notice);
+        }
+
+        eprintln!("Playing (press Ctrl+C to stop)");
+
+        while !self.should_quit {
+            self.tick()?;
+            std::thread::sleep(Duration::from_millis(100));
+        }
+
+        Ok(())
+    }
+
+    /// Play the mix and serve commands from clients on the control socket until stopped
+    #[cfg(unix)]
+    pub fn run_daemon(&mut self) -> Result<()> {
+        let server = daemon::Server::bind()?;
+
+        if let Some(notice) = self.device_notice.take() {
+            eprintln!("{}", notice);
+        }
+        self.handle_sounds_headless()?;
+        self.start_playback()?;
+        if let Some(notice) = self.sound_notice.take() {
+            eprintln!("{}", notice);
+        }
+
+        eprintln!("Daemon listening on {}", server.path().display());
+
+        while !self.should_quit {
+            if let Some(request) = server.next_request(Duration::from_millis(100)) {
+                let response = self.execute(request.command.clone()).into();
+                request.reply(response);
+            }
+            self.tick()?;
+        }
+
+        Ok(())
+    }
+
+    /// Mix `duration` of the saved mix, or of the session's preset, into a WAV or FLAC file.
+    /// This runs as fast as the mix can be computed, not in real time.
+    pub fn render_to_file(
+        session: Session,
+        dirs: Dirs,
+        path: &Path,
+        duration: Duration,
+    ) -> Result<()> {
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+        let flac = match extension.as_deref() {
+            Some("wav") => false,
+            Some("flac") => true,
+            _ => anyhow::bail!(
+                "Unsupported output file: {}, expected a .wav or .flac file",
+                path.display()
+            ),
+        };
+
+        // FLAC files have no such limit
+        let max = audio::max_wav_duration();
+        if !flac && duration > max {
+            anyhow::bail!(
+                "A WAV file can hold at most {}, render a shorter mix",
+                sleep::format_remaining(max)
+            );
+        }
+
+        // Rendering never changes the saved settings
+        let config = Config::load(&dirs.config_file)?;
+        if let Some(name) = &session.preset {
+            config.clone().apply_preset(name)?;
+        }
+
+        // Render next to the output, so an existing file is only replaced by a finished mix
+        let file_name = path
+            .file_name()
+            .context(format!("Invalid output file: {}", path.display()))?;
+        let partial = path.with_file_name(format!(".{}.part", file_name.to_string_lossy()));
+        let audio = if flac {
+            AudioPlayer::flac(&partial)?
+        } else {
+            AudioPlayer::wav(&partial)?
+        };
+        let mut app = Self::with_backend(Backend::Local(audio), dirs, config, session, false)?;
+
+        let result = app.render_mix(duration).and_then(|()| {
+            std::fs::rename(&partial, path).context(format!("Failed to save {}", path.display()))
+        });
+        if result.is_err() {
+            // Don't leave a broken file behind
+            let _ = std::fs::remove_file(&partial);
+        } else {
+            eprintln!("Saved {}", path.display());
+        }
+        result
+    }
+
+    fn render_mix(&mut self, duration: Duration) -> Result<()> {
+        self.handle_sounds_headless()?;
+        self.load_mix()?;
+        if let Some(notice) = self.sound_notice.take() {
+            eprintln!("{}", notice);
+        }
+
+        let Backend::Local(audio) = &mut self.backend else {
+            unreachable!("rendering always plays locally");
+        };
+
+        eprintln!("Rendering {} of audio", sleep::format_remaining(duration));
+
+        // Render in chunks so a long mix never has to fit in memory
+        let chunk = Duration::from_secs(1);
+        let mut rendered = Duration::ZERO;
+        let mut percent = None;
+        while rendered < duration {
+            let length = chunk.min(duration - rendered);
+            audio.render(length)?;
+            rendered += length;
+
+            let done = (rendered.as_secs_f64() / duration.as_secs_f64() * 100.0) as u32;
+            if percent != Some(done) {
+                percent = Some(done);
+                eprint!("\r{:>3}%", done);
+            }
+        }
+        eprintln!();
+
+        audio.finish()
+    }
+
+    /// Keep time based state up to date, called regularly from the main loop
+    fn tick(&mut self) -> Result<()> {
+        if let Backend::Local(audio) = &mut self.backend {
+            // Keep offline output in step with the clock, like a sound card would
+            let now = Instant::now();
+            audio.render(now - self.last_render)?;
+            self.last_render = now;
+        }
+
+        match self.backend {
+            Backend::Local(_) => {
+                self.update_sleep_timer();
+                self.update_mpris()
+            }
+            // Pick up changes made by other clients
+            #[cfg(unix)]
+            Backend::Remote(_) => self.dispatch(Command::Status),
+        }
+    }
+
+    /// Load all available channels, apply the session settings and start playing
+    fn start_playback(&mut self) -> Result<()> {
+        self.load_mix()?;
+        // Time spent before (e.g. downloading the sounds) was never meant to be heard
+        self.last_render = Instant::now();
+
+        if let Some(duration) = self.session.duration {
+            self.start_sleep_timer(duration);
+        }
+
+        // Media keys are a convenience, so playback goes on without a session bus
+        #[cfg(target_os = "linux")]
+        {
+            // Keep the tests off the desktop's media controls
+            if !cfg!(test) {
+                self.mpris = MprisServer::start(&self.config.master).ok();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Load all available channels with the session settings applied and unpause them
+    fn load_mix(&mut self) -> Result<()> {
+        // Without downloaded sounds only the generated channels can play
+        self.registry.retain_available(&self.dirs.sounds);
+
+        self.apply_session()?;
+
+        if let Backend::Local(audio) = &mut self.backend {
+            let skipped =
+                audio.load_sounds(self.registry.channels(), &self.config, &self.dirs.sounds)?;
+            self.skip_channels(skipped);
+        }
+
+        // Set initial volumes before anything is audible (pan is set when loading)
+        self.update_audio_volumes(Duration::ZERO);
+        self.set_paused(false);
+
+        Ok(())
+    }
+
+    /// Apply commands from desktop media controls and report master changes back to them
+    #[cfg(target_os = "linux")]
+    fn update_mpris(&mut self) -> Result<()> {
+        while let Some(command) = self.mpris.as_ref().and_then(|mpris| mpris.try_recv()) {
+            self.execute(command)?;
+        }
+
+        if let Some(mpris) = self.mpris.as_mut() {
+            if mpris.update(&self.config.master).is_err() {
+                // The bus went away, stop publishing
+                self.mpris = None;
+            }
+        }
+
+        Ok(())
+    }
 
+    #[cfg(not(target_os = "linux"))]
+    fn update_mpris(&mut self) -> Result<()> {
         Ok(())
     }
 
-    fn handle_key_event(&mut self, key: KeyEvent) -> Result<()> {
+    /// Apply the command line overrides to the in-memory config
+    fn apply_session(&mut self) -> Result<()> {
+        if let Some(name) = &self.session.preset {
+            self.config.apply_preset(name)?;
+            self.preset = Some(name.clone());
+        }
+
+        for (name, volume) in &self.session.volumes {
+            let channel = self.find_channel(name)?;
+            self.config.sound_mut(&channel).volume = *volume;
+        }
+
+        for name in &self.session.muted {
+            let channel = self.find_channel(name)?;
+            self.config.sound_mut(&channel).muted = true;
+        }
+
+        if let Some(master) = self.session.master {
+            self.config.master.volume = master;
+        }
+
+        Ok(())
+    }
+
+    /// Look up a channel by id or display name
+    fn find_channel(&self, name: &str) -> Result<ChannelDef> {
+        self.registry.find(name).cloned().ok_or_else(|| {
+            let available: Vec<&str> = self
+                .registry
+                .channels()
+                .iter()
+                .map(|c| c.id.as_str())
+                .collect();
+            anyhow::anyhow!(
+                "Unknown channel '{}'. Available channels: {}",
+                name,
+                available.join(", ")
+            )
+        })
+    }
+
+    fn handle_key_event(&mut self, key: KeyEvent, terminal: &mut DefaultTerminal) -> Result<()> {
         match key.code {
             // Quit
             KeyCode::Char('q') | KeyCode::Char('Q') => {
@@ -133,15 +545,102 @@ impl App {
             }
             // Volume up
             KeyCode::Up | KeyCode::Char('k') => {
-                self.increase_volume()?;
+                self.dispatch(Command::AdjustVolume {
+                    channel: self.selected_id(),
+                    delta: VOLUME_STEP,
+                })?;
             }
             // Volume down
             KeyCode::Down | KeyCode::Char('j') => {
-                self.decrease_volume()?;
+                self.dispatch(Command::AdjustVolume {
+                    channel: self.selected_id(),
+                    delta: -VOLUME_STEP,
+                })?;
             }
             // Toggle mute
             KeyCode::Char('m') | KeyCode::Char('M') => {
-                self.toggle_mute()?;
+                self.dispatch(Command::ToggleMute {
+                    channel: self.selected_id(),
+                })?;
+            }
+            // Pan left/right
+            KeyCode::Char(',') | KeyCode::Char('<') => {
+                self.pan_selected(-PAN_STEP)?;
+            }
+            KeyCode::Char('.') | KeyCode::Char('>') => {
+                self.pan_selected(PAN_STEP)?;
+            }
+            // Lower/raise the low-pass cutoff (more/less muffled)
+            KeyCode::Char('[') => {
+                self.step_low_pass(false)?;
+            }
+            KeyCode::Char(']') => {
+                self.step_low_pass(true)?;
+            }
+            // Lower/raise the high-pass cutoff
+            KeyCode::Char('{') => {
+                self.step_high_pass(false)?;
+            }
+            KeyCode::Char('}') => {
+                self.step_high_pass(true)?;
+            }
+            // Play/pause
+            KeyCode::Char(' ') => {
+                self.dispatch(Command::SetPaused {
+                    paused: !self.paused,
+                })?;
+            }
+            // Cycle sleep timer presets
+            KeyCode::Char('t') | KeyCode::Char('T') => {
+                self.cycle_sleep_timer()?;
+            }
+            // Lengthen/shorten sleep timer
+            KeyCode::Char('+') | KeyCode::Char('=') => {
+                self.extend_sleep_timer()?;
+            }
+            KeyCode::Char('-') => {
+                self.shorten_sleep_timer()?;
+            }
+            // Switch to the next preset
+            KeyCode::Char('p') | KeyCode::Char('P') => {
+                self.cycle_preset()?;
+            }
+            // Switch to the next output device
+            KeyCode::Char('o') | KeyCode::Char('O') => {
+                if let Err(error) = self.cycle_device() {
+                    prompt::run_prompt(
+                        terminal,
+                        "Output Device",
+                        &format!("{:#}", error),
+                        prompt::PromptType::Error,
+                    )?;
+                }
+            }
+            // Save the mix as a preset
+            KeyCode::Char('s') | KeyCode::Char('S') => {
+                let initial = self.preset.clone().unwrap_or_default();
+                if let Some(name) =
+                    prompt::run_text_prompt(terminal, "Save Preset", "Preset name:", &initial)?
+                {
+                    self.dispatch(Command::SavePreset { name })?;
+                }
+            }
+            // Delete the current preset, there's no undo so ask first
+            KeyCode::Char('x') | KeyCode::Char('X') => {
+                if let Some(name) = self.preset.clone() {
+                    let message = format!("Delete the preset \"{}\"?", name);
+                    if prompt::run_prompt(
+                        terminal,
+                        "Delete Preset",
+                        &message,
+                        prompt::PromptType::YesNo {
+                            yes: "delete",
+                            no: "keep it",
+                        },
+                    )? {
+                        self.dispatch(Command::DeletePreset { name })?;
+                    }
+                }
             }
             _ => {}
         }
@@ -149,10 +648,374 @@ impl App {
         Ok(())
     }
 
+    /// Apply a command to the player, wherever it runs
+    fn dispatch(&mut self, command: Command) -> Result<()> {
+        match &mut self.backend {
+            Backend::Local(_) => {
+                self.execute(command)?;
+            }
+            #[cfg(unix)]
+            Backend::Remote(client) => {
+                let status = client.send(&command)?;
+                self.apply_status(status);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Carry out a command on the local player and report the resulting state
+    pub fn execute(&mut self, command: Command) -> Result<Status> {
+        match command {
+            Command::Status => {}
+            Command::SetVolume { channel, volume } => {
+                let channel = self.resolve_channel(&channel)?;
+                if let Some(sound) = self.sound_config_mut(channel) {
+                    sound.volume = volume.min(100);
+                }
+                self.update_audio_volumes_and_save_config()?;
+            }
+            Command::AdjustVolume { channel, delta } => {
+                let channel = self.resolve_channel(&channel)?;
+                if let Some(sound) = self.sound_config_mut(channel) {
+                    sound.volume = (sound.volume as i16 + delta).clamp(0, 100) as u8;
+                }
+                self.update_audio_volumes_and_save_config()?;
+            }
+            Command::SetMute { channel, muted } => {
+                let channel = self.resolve_channel(&channel)?;
+                if let Some(sound) = self.sound_config_mut(channel) {
+                    sound.muted = muted;
+                }
+                self.update_audio_volumes_and_save_config()?;
+            }
+            Command::ToggleMute { channel } => {
+                let channel = self.resolve_channel(&channel)?;
+                if let Some(sound) = self.sound_config_mut(channel) {
+                    sound.muted = !sound.muted;
+                }
+                self.update_audio_volumes_and_save_config()?;
+            }
+            Command::SetPan { channel, pan } => {
+                let channel = self.resolve_sound_channel(&channel)?;
+                if let Some(sound) = self.sound_config_mut(channel) {
+                    sound.pan = pan.clamp(-100, 100);
+                }
+                self.update_audio_effects_and_save_config()?;
+            }
+            Command::AdjustPan { channel, delta } => {
+                let channel = self.resolve_sound_channel(&channel)?;
+                if let Some(sound) = self.sound_config_mut(channel) {
+                    sound.pan = (sound.pan as i16 + delta).clamp(-100, 100) as i8;
+                }
+                self.update_audio_effects_and_save_config()?;
+            }
+            Command::SetLowPass { channel, hz } => {
+                let channel = self.resolve_sound_channel(&channel)?;
+                check_cutoff(hz)?;
+                if let Some(sound) = self.sound_config_mut(channel) {
+                    sound.low_pass_hz = hz;
+                }
+                self.update_audio_effects_and_save_config()?;
+            }
+            Command::SetHighPass { channel, hz } => {
+                let channel = self.resolve_sound_channel(&channel)?;
+                check_cutoff(hz)?;
+                if let Some(sound) = self.sound_config_mut(channel) {
+                    sound.high_pass_hz = hz;
+                }
+                self.update_audio_effects_and_save_config()?;
+            }
+            Command::SetPaused { paused } => self.set_paused(paused),
+            Command::SetSleepTimer { seconds } => match seconds {
+                Some(seconds) if seconds > 0 => {
+                    let duration = Duration::from_secs(seconds);
+                    if duration > sleep::MAX_DURATION {
+                        anyhow::bail!(
+                            "Sleep timer can be set to at most {}",
+                            sleep::format_remaining(sleep::MAX_DURATION)
+                        );
+                    }
+                    self.start_sleep_timer(duration);
+                }
+                _ => self.cancel_sleep_timer(),
+            },
+            Command::LoadPreset { name } => {
+                self.config.apply_preset(&name)?;
+                self.preset = Some(name);
+                if self.persist_config {
+                    self.config.save(&self.dirs.config_file)?;
+                }
+                // Blend slowly into the new mix
+                self.update_audio_volumes(self.config.ramp.preset());
+                self.update_audio_effects();
+            }
+            Command::SavePreset { name } => {
+                let name = name.trim();
+                if name.is_empty() {
+                    anyhow::bail!("Preset name must not be empty");
+                }
+                self.config.save_preset(name);
+                self.preset = Some(name.to_string());
+                self.save_presets()?;
+            }
+            Command::DeletePreset { name } => {
+                if !self.config.delete_preset(&name) {
+                    anyhow::bail!("Unknown preset '{}'", name);
+                }
+                if self.preset.as_deref() == Some(name.as_str()) {
+                    self.preset = None;
+                }
+                self.save_presets()?;
+            }
+            Command::SetDevice { name } => {
+                self.switch_device(name.as_deref())?;
+                self.config.device = name.clone();
+                self.device = name;
+                if self.persist_config {
+                    self.config.save(&self.dirs.config_file)?;
+                }
+            }
+            Command::Stop => self.quit(),
+        }
+
+        Ok(self.status())
+    }
+
+    /// Snapshot of the current player state
+    pub fn status(&self) -> Status {
+        let channel_status = |channel: Channel| ChannelStatus {
+            id: self.channel_id(channel),
+            name: self.channel_name(channel).to_string(),
+            volume: self.get_volume(channel),
+            muted: self.is_muted(channel),
+            pan: self.get_pan(channel),
+            low_pass_hz: self.get_filters(channel).0,
+            high_pass_hz: self.get_filters(channel).1,
+        };
+
+        Status {
+            channels: (0..self.registry.len())
+                .map(|index| channel_status(Channel::Sound(index)))
+                .collect(),
+            master: channel_status(Channel::Master),
+            paused: self.paused,
+            sleep_timer: self.sleep_timer.as_ref().map(|timer| SleepStatus {
+                duration_secs: timer.duration().as_secs(),
+                remaining_secs: timer.remaining().as_secs(),
+            }),
+            presets: self.config.presets.keys().cloned().collect(),
+            preset: self.preset.clone(),
+            device: self.device.clone(),
+        }
+    }
+
+    /// Mirror the state reported by a daemon
+    #[cfg(unix)]
+    fn apply_status(&mut self, status: Status) {
+        for channel in &status.channels {
+            let sound = self.config.sounds.entry(channel.id.clone()).or_default();
+            sound.volume = channel.volume;
+            sound.muted = channel.muted;
+            sound.pan = channel.pan;
+            sound.low_pass_hz = channel.low_pass_hz;
+            sound.high_pass_hz = channel.high_pass_hz;
+        }
+        self.config.master.volume = status.master.volume;
+        self.config.master.muted = status.master.muted;
+
+        self.paused = status.paused;
+
+        // Only the names are needed, presets are always applied by the daemon
+        self.config
+            .presets
+            .retain(|name, _| status.presets.contains(name));
+        for name in status.presets {
+            self.config.presets.entry(name).or_default();
+        }
+        self.preset = status.preset;
+        self.device = status.device;
+
+        let fade = Duration::from_secs(self.config.sleep.fade_minutes * 60);
+        self.sleep_timer = status.sleep_timer.map(|timer| {
+            SleepTimer::resume(
+                Duration::from_secs(timer.duration_secs),
+                Duration::from_secs(timer.remaining_secs),
+                fade,
+            )
+        });
+    }
+
+    /// Look up a channel by id or display name, "master" selects the master volume
+    fn resolve_channel(&self, name: &str) -> Result<Channel> {
+        if name.eq_ignore_ascii_case(control::MASTER) {
+            return Ok(Channel::Master);
+        }
+
+        let channel = self.find_channel(name)?;
+        let index = self
+            .registry
+            .channels()
+            .iter()
+            .position(|def| def.id == channel.id)
+            .unwrap_or_default();
+
+        Ok(Channel::Sound(index))
+    }
+
+    /// Like `resolve_channel`, but only for sound channels
+    fn resolve_sound_channel(&self, name: &str) -> Result<Channel> {
+        match self.resolve_channel(name)? {
+            Channel::Master => anyhow::bail!("The master volume has no pan or filters"),
+            channel => Ok(channel),
+        }
+    }
+
+    /// Move the selected channel in the stereo field. The master volume has no pan.
+    fn pan_selected(&mut self, delta: i16) -> Result<()> {
+        if self.selected_channel == Channel::Master {
+            return Ok(());
+        }
+
+        self.dispatch(Command::AdjustPan {
+            channel: self.selected_id(),
+            delta,
+        })
+    }
+
+    /// Move the selected channel's low-pass cutoff by one step
+    fn step_low_pass(&mut self, raise: bool) -> Result<()> {
+        if self.selected_channel == Channel::Master {
+            return Ok(());
+        }
+
+        let (current, _) = self.get_filters(self.selected_channel);
+        self.dispatch(Command::SetLowPass {
+            channel: self.selected_id(),
+            hz: filter::step_low_pass(current, raise),
+        })
+    }
+
+    /// Move the selected channel's high-pass cutoff by one step
+    fn step_high_pass(&mut self, raise: bool) -> Result<()> {
+        if self.selected_channel == Channel::Master {
+            return Ok(());
+        }
+
+        let (_, current) = self.get_filters(self.selected_channel);
+        self.dispatch(Command::SetHighPass {
+            channel: self.selected_id(),
+            hz: filter::step_high_pass(current, raise),
+        })
+    }
+
+    /// Id of the selected channel, as used in commands
+    fn selected_id(&self) -> String {
+        self.channel_id(self.selected_channel)
+    }
+
+    /// Check if sounds are missing and download them, reporting progress on stderr.
+    /// Updates are never installed without asking, so they are only mentioned.
+    fn handle_sounds_headless(&mut self) -> Result<()> {
+        if download::is_cwd_sounds(&self.dirs.sounds) {
+            eprintln!("Debug Mode: Using sounds from ./sounds/ directory");
+            return Ok(());
+        }
+
+        let current_version = env!("CARGO_PKG_VERSION");
+        let mut reporter = download::ConsoleReporter::default();
+
+        if download::sounds_exist(&self.dirs.sounds) {
+            if !self.needs_sounds_check(current_version) {
+                return Ok(());
+            }
+
+            let installed = self.config.sounds_version.clone();
+            match download::available_pack(&mut reporter, &self.config.download, current_version)
+            {
+                Ok(available) => match soundpack::check_update(installed.as_deref(), &available) {
+                    UpdateCheck::UpToDate => self.record_sounds_checked(current_version)?,
+                    UpdateCheck::Update => reporter.report(
+                        "Update Available",
+                        &format!(
+                            "New sounds available: v{} (installed: {}). Run relax-player without --headless to download them.",
+                            available.version,
+                            installed.as_deref().unwrap_or("unknown")
+                        ),
+                    )?,
+                    UpdateCheck::Downgrade(installed) => reporter.report(
+                        "Newer Sounds Installed",
+                        &format!(
+                            "The sounds for this version (v{}) are older than the installed ones (v{}), keeping the installed sounds. Run relax-player without --headless to replace them.",
+                            available.version, installed
+                        ),
+                    )?,
+                },
+                Err(error) => {
+                    self.record_sounds_check_failed()?;
+                    reporter.report(
+                        "Update Check Failed",
+                        &format!(
+                            "Couldn't check for sound updates, trying again tomorrow: {:#}",
+                            error
+                        ),
+                    )?
+                }
+            }
+            return Ok(());
+        }
+
+        match download::download_sounds(
+            &mut reporter,
+            &self.config.download,
+            current_version,
+            &self.dirs.sounds,
+        ) {
+            Ok(pack) => self.record_sounds(&pack, current_version),
+            Err(error) => {
+                let help = download::failure_help(&error).1;
+                reporter.report(
+                    "Error",
+                    &format!(
+                        "Failed to download required sound files: {:#}\n{}Only generated noise is available.",
+                        error,
+                        help.map(|help| format!("{} ", help)).unwrap_or_default()
+                    ),
+                )
+            }
+        }
+    }
+
+    /// Remember which sound pack is installed, and that it's the one for this player
+    fn record_sounds(&mut self, pack: &PackManifest, player_version: &str) -> Result<()> {
+        self.config.sounds_version = Some(pack.version.to_string());
+        self.record_sounds_checked(player_version)
+    }
+
+    /// Remember that the installed sounds are right for this player, until it's updated
+    fn record_sounds_checked(&mut self, player_version: &str) -> Result<()> {
+        self.config.sounds_checked = Some(player_version.to_string());
+        self.config.sounds_check_failed = None;
+        self.config.save(&self.dirs.config_file)
+    }
+
+    /// Whether to look for sound updates: the player changed since the last check, and the
+    /// check didn't already fail today
+    fn needs_sounds_check(&self, player_version: &str) -> bool {
+        self.config.sounds_checked.as_deref() != Some(player_version)
+            && self.config.sounds_check_failed != Some(today())
+    }
+
+    /// Remember that the update check failed, so it isn't tried again until tomorrow
+    fn record_sounds_check_failed(&mut self) -> Result<()> {
+        self.config.sounds_check_failed = Some(today());
+        self.config.save(&self.dirs.config_file)
+    }
+
     /// Check if sounds need downloading and download
     fn handle_sounds(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
-        // In debug mode, check CWD first and skip download if found
-        if cfg!(debug_assertions) && download::check_cwd_sounds() {
+        // In debug mode, skip downloading if the sounds in the CWD are used
+        if download::is_cwd_sounds(&self.dirs.sounds) {
             prompt::run_prompt(
                 terminal,
                 "Debug Mode",
@@ -163,8 +1026,8 @@ impl App {
         }
 
         let current_version = env!("CARGO_PKG_VERSION");
-        let sounds_exist = download::sounds_exist()?;
-        let stored_version = self.config.sounds_version.as_deref();
+        let sounds_exist = download::sounds_exist(&self.dirs.sounds);
+        let installed = self.config.sounds_version.clone();
         let prompt_title = "Download Sounds";
 
         // Check if we need to download sounds
@@ -175,45 +1038,82 @@ impl App {
             );
 
             prompt::run_prompt(terminal, prompt_title, &message, prompt::PromptType::Info)?
-        } else if download::needs_update(current_version, stored_version) {
-            // Version mismatch - prompt user
-            let message = format!(
-                "New sounds available for v{} (current: {}). Download now?",
-                current_version,
-                stored_version.unwrap_or("unknown")
-            );
+        } else if self.needs_sounds_check(current_version) {
+            // The player changed since the last check, see if the sounds did too. Without a
+            // connection the check is tried again the next day.
+            let available =
+                download::available_pack(terminal, &self.config.download, current_version);
+
+            match available.map(|pack| (soundpack::check_update(installed.as_deref(), &pack), pack))
+            {
+                Ok((UpdateCheck::UpToDate, _)) => {
+                    self.record_sounds_checked(current_version)?;
+                    false
+                }
+                Ok((UpdateCheck::Update, available)) => {
+                    let message = format!(
+                        "New sounds available: v{} (installed: {}). Download now?",
+                        available.version,
+                        installed.as_deref().unwrap_or("unknown")
+                    );
+
+                    prompt::run_prompt(terminal, prompt_title, &message, DOWNLOAD_PROMPT)?
+                }
+                Ok((UpdateCheck::Downgrade(installed), available)) => {
+                    let message = format!(
+                        "The sounds for this version (v{}) are older than the installed ones (v{}). Replace them with the older sounds?",
+                        available.version, installed
+                    );
 
-            prompt::run_prompt(terminal, prompt_title, &message, prompt::PromptType::YesNo)?
+                    let replace =
+                        prompt::run_prompt(terminal, prompt_title, &message, DOWNLOAD_PROMPT)?;
+                    if !replace {
+                        // Keeping newer sounds is a choice, don't ask again
+                        self.record_sounds_checked(current_version)?;
+                    }
+                    replace
+                }
+                Err(_) => {
+                    self.record_sounds_check_failed()?;
+                    false
+                }
+            }
         } else {
             // All good, sounds exist and version matches
             false
         };
 
         if should_download {
-            match download::download_sounds(terminal, GITHUB_USER, GITHUB_REPO, current_version) {
-                Ok(()) => {
+            match download::download_sounds(
+                terminal,
+                &self.config.download,
+                current_version,
+                &self.dirs.sounds,
+            ) {
+                Ok(pack) => {
                     // Update config with new version
-                    self.config.sounds_version = Some(current_version.to_string());
-                    self.config.save()?;
+                    self.record_sounds(&pack, current_version)?;
                 }
-                Err(error) => {
+                Err(error) if error.is::<download::DownloadCancelled>() => {
                     if !sounds_exist {
-                        // Show error in TUI prompt
-                        let error_msg = format!(
-                            "Failed to download required sound files.\n\nError: {}\n\nCheck your internet connection and try again.",
-                            error
-                        );
                         prompt::run_prompt(
                             terminal,
-                            "Error",
-                            &error_msg,
+                            "Download Cancelled",
+                            "Only generated noise is available. Restart to download the sounds.",
                             prompt::PromptType::Error,
                         )?;
-
-                        // Clean up terminal before exiting
-                        ratatui::restore();
-
-                        return Err(error.context("Failed to download required sound files"));
+                    }
+                }
+                Err(error) => {
+                    if !sounds_exist {
+                        // Show error in TUI prompt, the generated noise channels still work
+                        let (title, help) = download::failure_help(&error);
+                        let error_msg = format!(
+                            "Failed to download required sound files.\n\nError: {:#}\n\n{}Restart to try again, only generated noise is available until then.",
+                            error,
+                            help.map(|help| format!("{} ", help)).unwrap_or_default()
+                        );
+                        prompt::run_prompt(terminal, title, &error_msg, prompt::PromptType::Error)?;
                     }
                 }
             }
@@ -222,114 +1122,688 @@ impl App {
         Ok(())
     }
 
+    /// Get all mixer columns in display order (sound channels followed by master)
+    pub fn channels(&self) -> Vec<Channel> {
+        (0..self.registry.len())
+            .map(Channel::Sound)
+            .chain(std::iter::once(Channel::Master))
+            .collect()
+    }
+
+    /// Get the id of a channel, as used in the config and in commands
+    pub fn channel_id(&self, channel: Channel) -> String {
+        match channel {
+            Channel::Sound(index) => self
+                .registry
+                .get(index)
+                .map_or_else(String::new, |def| def.id.clone()),
+            Channel::Master => control::MASTER.to_string(),
+        }
+    }
+
+    /// Get the name of a channel for display
+    pub fn channel_name(&self, channel: Channel) -> &str {
+        match channel {
+            Channel::Sound(index) => self
+                .registry
+                .get(index)
+                .map_or("Unknown", |def| def.name.as_str()),
+            Channel::Master => "Master",
+        }
+    }
+
     /// Move selection to the next channel (right)
     fn select_next(&mut self) {
-        self.selected_channel = self.selected_channel.next();
+        self.selected_channel = match self.selected_channel {
+            Channel::Sound(index) if index + 1 < self.registry.len() => Channel::Sound(index + 1),
+            _ => Channel::Master,
+        };
     }
 
     /// Move selection to the previous channel (left)
     fn select_prev(&mut self) {
-        self.selected_channel = self.selected_channel.prev();
+        self.selected_channel = match self.selected_channel {
+            Channel::Sound(index) => Channel::Sound(index.saturating_sub(1)),
+            Channel::Master if !self.registry.is_empty() => Channel::Sound(self.registry.len() - 1),
+            Channel::Master => Channel::Master,
+        };
     }
 
     fn update_audio_volumes_and_save_config(&self) -> Result<()> {
-        self.config.save()?;
-        self.update_audio_volumes();
+        if self.persist_config {
+            self.config.save(&self.dirs.config_file)?;
+        }
+        self.update_audio_volumes(self.config.ramp.volume());
 
         Ok(())
     }
 
-    fn update_audio_volumes(&self) {
-        let rain_vol = self.config.effective_volume(Channel::Rain);
-        let thunder_vol = self.config.effective_volume(Channel::Thunder);
-        let campfire_vol = self.config.effective_volume(Channel::Campfire);
+    /// Write new or deleted presets to the config file.
+    /// When the session overrides keep the config from being saved, only the presets are written.
+    fn save_presets(&self) -> Result<()> {
+        if self.persist_config {
+            return self.config.save(&self.dirs.config_file);
+        }
 
-        self.audio
-            .update_volumes(rain_vol, thunder_vol, campfire_vol);
+        let mut saved = Config::load(&self.dirs.config_file)?;
+        saved.presets = self.config.presets.clone();
+        saved.save(&self.dirs.config_file)
     }
 
-    /// Increase volume of selected channel
-    fn increase_volume(&mut self) -> Result<()> {
-        match self.selected_channel {
-            Channel::Rain => {
-                self.config.rain.volume = (self.config.rain.volume + 5).min(100);
-            }
-            Channel::Thunder => {
-                self.config.thunder.volume = (self.config.thunder.volume + 5).min(100);
-            }
-            Channel::Campfire => {
-                self.config.campfire.volume = (self.config.campfire.volume + 5).min(100);
-            }
-            Channel::Master => {
-                self.config.master.volume = (self.config.master.volume + 5).min(100);
-            }
+    fn update_audio_effects_and_save_config(&self) -> Result<()> {
+        if self.persist_config {
+            self.config.save(&self.dirs.config_file)?;
         }
-        self.update_audio_volumes_and_save_config()?;
+        self.update_audio_effects();
+
         Ok(())
     }
 
-    /// Decrease volume of selected channel
-    fn decrease_volume(&mut self) -> Result<()> {
-        match self.selected_channel {
-            Channel::Rain => {
-                self.config.rain.volume = self.config.rain.volume.saturating_sub(5);
-            }
-            Channel::Thunder => {
-                self.config.thunder.volume = self.config.thunder.volume.saturating_sub(5);
-            }
-            Channel::Campfire => {
-                self.config.campfire.volume = self.config.campfire.volume.saturating_sub(5);
+    /// Apply the configured pan and tone filters to the audio
+    fn update_audio_effects(&self) {
+        let Backend::Local(audio) = &self.backend else {
+            return;
+        };
+
+        for def in self.registry.channels() {
+            let Some(sound) = self.config.sound(&def.id) else {
+                continue;
+            };
+            audio.set_pan(&def.id, sound.pan as f32 / 100.0);
+            audio.set_filter(&def.id, sound.low_pass_hz, sound.high_pass_hz);
+        }
+    }
+
+    /// Move the audio to the configured volumes over `ramp`
+    fn update_audio_volumes(&self, ramp: Duration) {
+        let Backend::Local(audio) = &self.backend else {
+            return;
+        };
+
+        // The sleep timer fades everything out on top of the configured volumes
+        let fade = self
+            .sleep_timer
+            .as_ref()
+            .map_or(1.0, |timer| timer.volume_factor());
+
+        audio.update_volumes(
+            self.registry.channels().iter().map(|def| {
+                (
+                    def.id.as_str(),
+                    self.config.effective_volume(&def.id) * fade,
+                )
+            }),
+            ramp,
+        );
+    }
+
+    /// Pause or resume playback
+    fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+
+        if let Backend::Local(audio) = &self.backend {
+            if paused {
+                audio.pause();
+            } else {
+                audio.play();
             }
-            Channel::Master => {
-                self.config.master.volume = self.config.master.volume.saturating_sub(5);
+        }
+    }
+
+    /// Start a sleep timer with the given duration, replacing any running timer
+    pub fn start_sleep_timer(&mut self, duration: Duration) {
+        let fade = Duration::from_secs(self.config.sleep.fade_minutes * 60);
+        self.sleep_timer = Some(SleepTimer::new(duration, fade));
+        self.update_audio_volumes(self.config.ramp.volume());
+    }
+
+    /// Cancel the sleep timer and restore full volume
+    fn cancel_sleep_timer(&mut self) {
+        self.sleep_timer = None;
+        self.update_audio_volumes(self.config.ramp.volume());
+    }
+
+    /// Step through the sleep timer presets: off -> 15 -> 30 -> 60 -> 90 -> off
+    fn cycle_sleep_timer(&mut self) -> Result<()> {
+        let current = self
+            .sleep_timer
+            .as_ref()
+            .map_or(Duration::ZERO, |timer| timer.duration());
+
+        let next = sleep::PRESET_MINUTES
+            .iter()
+            .map(|minutes| minutes * 60)
+            .find(|preset| *preset > current.as_secs());
+
+        self.dispatch(Command::SetSleepTimer { seconds: next })
+    }
+
+    /// Add time to the sleep timer, starting one if none is running
+    fn extend_sleep_timer(&mut self) -> Result<()> {
+        let remaining = self
+            .sleep_timer
+            .as_ref()
+            .map_or(Duration::ZERO, |timer| timer.remaining());
+
+        self.dispatch(Command::SetSleepTimer {
+            seconds: Some(
+                (remaining + sleep::ADJUST_STEP)
+                    .min(sleep::MAX_DURATION)
+                    .as_secs(),
+            ),
+        })
+    }
+
+    /// Remove time from the sleep timer, cancelling it if nothing would be left
+    fn shorten_sleep_timer(&mut self) -> Result<()> {
+        let Some(timer) = self.sleep_timer.as_ref() else {
+            return Ok(());
+        };
+
+        let seconds = timer
+            .remaining()
+            .checked_sub(sleep::ADJUST_STEP)
+            .filter(|remaining| !remaining.is_zero())
+            .map(|remaining| remaining.as_secs());
+
+        self.dispatch(Command::SetSleepTimer { seconds })
+    }
+
+    /// Fade out while the sleep timer is ending and stop once it runs out
+    fn update_sleep_timer(&mut self) {
+        let Some(timer) = self.sleep_timer.as_ref() else {
+            return;
+        };
+
+        if timer.is_finished() {
+            self.sleep_timer = None;
+            match self.sleep_action {
+                SleepAction::Quit => self.quit(),
+                SleepAction::Pause => {
+                    self.set_paused(true);
+                    // Restore volumes so playback resumes at the normal level
+                    self.update_audio_volumes(self.config.ramp.volume());
+                }
             }
+        } else if timer.is_fading() {
+            self.update_audio_volumes(SLEEP_FADE_RAMP);
         }
-        self.update_audio_volumes_and_save_config()?;
+    }
+
+    /// Switch to the next output device, going back to the system default after the last one
+    fn cycle_device(&mut self) -> Result<()> {
+        let devices = audio::output_devices()?;
+
+        let next = match &self.device {
+            Some(current) => devices
+                .iter()
+                .position(|device| device == current)
+                .and_then(|index| devices.get(index + 1)),
+            None => devices.first(),
+        };
+
+        self.dispatch(Command::SetDevice {
+            name: next.cloned(),
+        })
+    }
+
+    /// Move playback to another output device, keeping the mix and pause state
+    fn switch_device(&mut self, device: Option<&str>) -> Result<()> {
+        let Backend::Local(audio) = &mut self.backend else {
+            return Ok(());
+        };
+        let skipped = audio.switch_device(
+            device,
+            self.registry.channels(),
+            &self.config,
+            &self.dirs.sounds,
+        )?;
+        self.skip_channels(skipped);
+
+        // Fade in on the new device instead of starting at full volume
+        self.update_audio_volumes(self.config.ramp.volume());
+        self.set_paused(self.paused);
+
         Ok(())
     }
 
-    /// Toggle mute for selected channel
-    fn toggle_mute(&mut self) -> Result<()> {
-        match self.selected_channel {
-            Channel::Rain => {
-                self.config.rain.muted = !self.config.rain.muted;
-            }
-            Channel::Thunder => {
-                self.config.thunder.muted = !self.config.thunder.muted;
-            }
-            Channel::Campfire => {
-                self.config.campfire.muted = !self.config.campfire.muted;
+    /// Leave out channels whose sounds couldn't be loaded and remember to tell the user
+    fn skip_channels(&mut self, skipped: Vec<SkippedChannel>) {
+        if skipped.is_empty() {
+            return;
+        }
+
+        let mut notice = String::from("Some sounds couldn't be loaded and are left out:");
+        for channel in skipped {
+            self.registry.remove(&channel.id);
+            notice += &format!("\n{}: {:#}", channel.name, channel.error);
+        }
+        self.sound_notice = Some(notice);
+
+        if let Channel::Sound(index) = self.selected_channel {
+            if index >= self.registry.len() {
+                self.selected_channel = if self.registry.is_empty() {
+                    Channel::Master
+                } else {
+                    Channel::Sound(self.registry.len() - 1)
+                };
             }
-            Channel::Master => {
-                self.config.master.muted = !self.config.master.muted;
+        }
+    }
+
+    /// Switch to the preset after the current one, in alphabetical order
+    fn cycle_preset(&mut self) -> Result<()> {
+        let mut names = self.config.presets.keys();
+        let next = match &self.preset {
+            Some(current) => names
+                .clone()
+                .find(|name| name.as_str() > current.as_str())
+                .or_else(|| names.next()),
+            None => names.next(),
+        };
+
+        let Some(name) = next.cloned() else {
+            return Ok(());
+        };
+        self.dispatch(Command::LoadPreset { name })
+    }
+
+    /// Get the settings for a channel
+    fn sound_config(&self, channel: Channel) -> Option<&SoundConfig> {
+        match channel {
+            Channel::Sound(index) => self
+                .registry
+                .get(index)
+                .and_then(|def| self.config.sound(&def.id)),
+            Channel::Master => Some(&self.config.master),
+        }
+    }
+
+    /// Get the settings for a channel for modification
+    fn sound_config_mut(&mut self, channel: Channel) -> Option<&mut SoundConfig> {
+        match channel {
+            Channel::Sound(index) => {
+                let def = self.registry.get(index)?;
+                Some(self.config.sound_mut(def))
             }
+            Channel::Master => Some(&mut self.config.master),
         }
-        self.update_audio_volumes_and_save_config()?;
-        Ok(())
     }
 
     /// Get the volume for a channel (0-100)
     pub fn get_volume(&self, channel: Channel) -> u8 {
+        self.sound_config(channel).map_or(0, |sound| sound.volume)
+    }
+
+    /// Get the low-pass and high-pass cutoffs of a channel, always off for master
+    pub fn get_filters(&self, channel: Channel) -> (Option<u32>, Option<u32>) {
         match channel {
-            Channel::Rain => self.config.rain.volume,
-            Channel::Thunder => self.config.thunder.volume,
-            Channel::Campfire => self.config.campfire.volume,
-            Channel::Master => self.config.master.volume,
+            Channel::Sound(_) => self.sound_config(channel).map_or((None, None), |sound| {
+                (sound.low_pass_hz, sound.high_pass_hz)
+            }),
+            Channel::Master => (None, None),
         }
     }
 
-    /// Check if a channel is muted
-    pub fn is_muted(&self, channel: Channel) -> bool {
+    /// Get the stereo position of a channel (-100 to 100), always centered for master
+    pub fn get_pan(&self, channel: Channel) -> i8 {
         match channel {
-            Channel::Rain => self.config.rain.muted,
-            Channel::Thunder => self.config.thunder.muted,
-            Channel::Campfire => self.config.campfire.muted,
-            Channel::Master => self.config.master.muted,
+            Channel::Sound(_) => self.sound_config(channel).map_or(0, |sound| sound.pan),
+            Channel::Master => 0,
         }
     }
 
+    /// Check if a channel is muted
+    pub fn is_muted(&self, channel: Channel) -> bool {
+        self.sound_config(channel).is_some_and(|sound| sound.muted)
+    }
+
     /// Quit the application
     fn quit(&mut self) {
         self.should_quit = true;
     }
 }
+
+/// Check that a filter cutoff is within the audible range
+fn check_cutoff(hz: Option<u32>) -> Result<()> {
+    match hz {
+        Some(hz) if !(filter::MIN_CUTOFF_HZ..=filter::MAX_CUTOFF_HZ).contains(&hz) => {
+            anyhow::bail!(
+                "Invalid cutoff {} Hz, expected {}-{} Hz",
+                hz,
+                filter::MIN_CUTOFF_HZ,
+                filter::MAX_CUTOFF_HZ
+            )
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Days since 1970, to tell whether something happened today
+fn today() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs() / (24 * 60 * 60))
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+    use std::fs;
+    use std::path::Path;
+
+    /// Id of the test tone, registered as a custom sound
+    const TONE: &str = "custom:tone.wav";
+
+    /// Config file and sounds in a temporary directory, with a test tone as custom sound
+    fn test_dirs(dir: &Path) -> Dirs {
+        let dirs = Dirs {
+            config_file: dir.join("config.json"),
+            sounds: dir.join("sounds"),
+        };
+
+        let custom_dir = dirs.custom_sounds();
+        fs::create_dir_all(&custom_dir).unwrap();
+        write_tone(&custom_dir.join("tone.wav"));
+        fs::write(custom_dir.join("broken.mp3"), b"not a sound").unwrap();
+
+        dirs
+    }
+
+    /// Write one second of a 440 Hz sine wave
+    fn write_tone(path: &Path) {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 44100,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(path, spec).unwrap();
+        for i in 0..spec.sample_rate {
+            let sample = (2.0 * PI * 440.0 * i as f32 / spec.sample_rate as f32).sin() * 0.5;
+            writer
+                .write_sample((sample * i16::MAX as f32) as i16)
+                .unwrap();
+        }
+        writer.finalize().unwrap();
+    }
+
+    /// Create an app playing on the given output, without saving any changes
+    fn playing_app(dir: &Path, audio: AudioPlayer) -> App {
+        let mut app = App::with_backend(
+            Backend::Local(audio),
+            test_dirs(dir),
+            Config::default(),
+            Session::default(),
+            false,
+        )
+        .unwrap();
+        app.start_playback().unwrap();
+        app
+    }
+
+    fn audio(app: &mut App) -> &mut AudioPlayer {
+        match &mut app.backend {
+            Backend::Local(audio) => audio,
+            #[cfg(unix)]
+            Backend::Remote(_) => unreachable!(),
+        }
+    }
+
+    fn channel_status(status: &Status, id: &str) -> ChannelStatus {
+        status
+            .channels
+            .iter()
+            .find(|channel| channel.id == id)
+            .cloned()
+            .unwrap()
+    }
+
+    /// Loudness of the samples of a WAV file in `range`, in seconds
+    fn rms(path: &Path, range: std::ops::Range<f32>) -> f32 {
+        let mut reader = hound::WavReader::open(path).unwrap();
+        let spec = reader.spec();
+        let samples_per_sec = (spec.sample_rate * spec.channels as u32) as f32;
+        let start = (range.start * samples_per_sec) as usize;
+        let end = (range.end * samples_per_sec) as usize;
+
+        let samples: Vec<f32> = reader
+            .samples::<i16>()
+            .skip(start)
+            .take(end - start)
+            .map(|sample| sample.unwrap() as f32 / i16::MAX as f32)
+            .collect();
+        assert_eq!(samples.len(), end - start, "WAV file is too short");
+
+        (samples.iter().map(|sample| sample * sample).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn commands_update_status_without_sound_card() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut app = playing_app(dir.path(), AudioPlayer::null());
+
+        // Custom sounds are loaded next to the built-in generators
+        let status = app.status();
+        assert_eq!(channel_status(&status, TONE).volume, 70);
+        assert!(status.channels.iter().any(|c| c.id == "white-noise"));
+
+        let status = app
+            .execute(Command::AdjustVolume {
+                channel: TONE.to_string(),
+                delta: -20,
+            })
+            .unwrap();
+        assert_eq!(channel_status(&status, TONE).volume, 50);
+
+        let status = app
+            .execute(Command::ToggleMute {
+                channel: control::MASTER.to_string(),
+            })
+            .unwrap();
+        assert!(status.master.muted);
+
+        let status = app
+            .execute(Command::SetVolume {
+                channel: control::MASTER.to_string(),
+                volume: 150,
+            })
+            .unwrap();
+        assert_eq!(status.master.volume, 100);
+
+        assert!(app
+            .execute(Command::SetVolume {
+                channel: "nope".to_string(),
+                volume: 10,
+            })
+            .is_err());
+
+        // Rendering keeps the null output in step with the clock
+        app.tick().unwrap();
+    }
+
+    #[test]
+    fn config_stays_in_the_given_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut app = playing_app(dir.path(), AudioPlayer::null());
+        assert!(!app.dirs.config_file.exists());
+
+        // Presets are saved even while the rest of the config isn't
+        app.execute(Command::SavePreset {
+            name: "Calm".to_string(),
+        })
+        .unwrap();
+        let saved = Config::load(&app.dirs.config_file).unwrap();
+        assert!(saved.presets.contains_key("Calm"));
+        assert!(saved.sound(TONE).is_none());
+    }
+
+    #[test]
+    fn overlong_sleep_timers_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut app = playing_app(dir.path(), AudioPlayer::null());
+
+        assert!(app
+            .execute(Command::SetSleepTimer {
+                seconds: Some(u64::MAX),
+            })
+            .is_err());
+        assert!(app.sleep_timer.is_none());
+
+        // A daemon reporting a huge timer doesn't take the client down either
+        let timer = SleepTimer::resume(Duration::MAX, Duration::MAX, Duration::ZERO);
+        assert!(timer.remaining() <= sleep::MAX_DURATION);
+    }
+
+    #[test]
+    fn failed_update_checks_wait_a_day() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut app = playing_app(dir.path(), AudioPlayer::null());
+        assert!(app.needs_sounds_check("9.9.9"));
+
+        app.record_sounds_check_failed().unwrap();
+        assert!(!app.needs_sounds_check("9.9.9"));
+
+        // Tried again the next day
+        app.config.sounds_check_failed = Some(today() - 1);
+        assert!(app.needs_sounds_check("9.9.9"));
+
+        app.record_sounds_checked("9.9.9").unwrap();
+        assert!(!app.needs_sounds_check("9.9.9"));
+        let saved = Config::load(&app.dirs.config_file).unwrap();
+        assert_eq!(saved.sounds_check_failed, None);
+    }
+
+    #[test]
+    fn mix_is_rendered_to_flac() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mix.flac");
+        let mut app = playing_app(dir.path(), AudioPlayer::flac(&path).unwrap());
+
+        audio(&mut app).render(Duration::from_secs(1)).unwrap();
+        audio(&mut app).finish().unwrap();
+
+        let mut reader = claxon::FlacReader::open(&path).unwrap();
+        assert_eq!(reader.streaminfo().channels, 2);
+        assert_eq!(reader.streaminfo().samples, Some(44100));
+        let samples: Vec<i32> = reader.samples().map(|sample| sample.unwrap()).collect();
+        let loudest = samples.iter().map(|sample| sample.abs()).max().unwrap();
+        assert!(loudest > 1000, "tone should be audible, got {}", loudest);
+    }
+
+    #[test]
+    fn failed_render_keeps_existing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("storm.wav");
+        fs::write(&path, b"my old mix").unwrap();
+
+        let session = Session {
+            preset: Some("Strom".to_string()),
+            ..Session::default()
+        };
+        let error = App::render_to_file(
+            session,
+            test_dirs(dir.path()),
+            &path,
+            Duration::from_secs(1),
+        )
+        .unwrap_err();
+
+        assert!(error.to_string().contains("Unknown preset"), "{}", error);
+        assert_eq!(fs::read(&path).unwrap(), b"my old mix");
+        assert!(!dir.path().join(".storm.wav.part").exists());
+    }
+
+    #[test]
+    fn offline_output_starts_with_playback() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mix.wav");
+        let mut app = App::with_backend(
+            Backend::Local(AudioPlayer::wav(&path).unwrap()),
+            test_dirs(dir.path()),
+            Config::default(),
+            Session::default(),
+            false,
+        )
+        .unwrap();
+
+        // As if the sounds took a minute to download
+        app.last_render = Instant::now() - Duration::from_secs(60);
+        app.start_playback().unwrap();
+        app.tick().unwrap();
+        audio(&mut app).finish().unwrap();
+
+        let reader = hound::WavReader::open(&path).unwrap();
+        assert!(reader.duration() < reader.spec().sample_rate);
+    }
+
+    #[test]
+    fn broken_sounds_are_left_out() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut app = playing_app(dir.path(), AudioPlayer::null());
+
+        assert!(app.registry.find("custom:broken.mp3").is_none());
+        assert!(app.registry.find(TONE).is_some());
+        let notice = app.sound_notice.take().unwrap();
+        assert!(notice.contains("broken"), "{}", notice);
+    }
+
+    #[test]
+    fn volume_and_mute_changes_are_heard() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mix.wav");
+        let mut app = playing_app(dir.path(), AudioPlayer::wav(&path).unwrap());
+
+        let set_volume = |app: &mut App, volume| {
+            app.execute(Command::SetVolume {
+                channel: TONE.to_string(),
+                volume,
+            })
+            .unwrap();
+        };
+        let render = |app: &mut App| audio(app).render(Duration::from_millis(500)).unwrap();
+
+        set_volume(&mut app, 100);
+        render(&mut app);
+
+        // Volume changes ramp, so only the end of each step is at the new volume
+        set_volume(&mut app, 25);
+        render(&mut app);
+
+        app.execute(Command::SetMute {
+            channel: TONE.to_string(),
+            muted: true,
+        })
+        .unwrap();
+        render(&mut app);
+
+        // Muting the master silences everything, even unmuted channels
+        app.execute(Command::SetMute {
+            channel: TONE.to_string(),
+            muted: false,
+        })
+        .unwrap();
+        app.execute(Command::SetMute {
+            channel: control::MASTER.to_string(),
+            muted: true,
+        })
+        .unwrap();
+        render(&mut app);
+
+        audio(&mut app).finish().unwrap();
+
+        let full = rms(&path, 0.4..0.5);
+        let quiet = rms(&path, 0.9..1.0);
+        assert!(full > 0.1, "tone should be audible, got {}", full);
+        assert!(
+            quiet > 0.01 && quiet < full * 0.5,
+            "tone should be quieter, got {} after {}",
+            quiet,
+            full
+        );
+        assert_eq!(rms(&path, 1.4..1.5), 0.0);
+        assert_eq!(rms(&path, 1.9..2.0), 0.0);
+    }
+}
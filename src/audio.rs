@@ -1,94 +1,437 @@
 use anyhow::{Context, Result};
+use rodio::cpal::traits::{DeviceTrait, HostTrait};
+use rodio::dynamic_mixer::{self, DynamicMixer, DynamicMixerController};
 use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink, Source};
-use std::fs::File;
-use std::io::BufReader;
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 
-use crate::download;
+use crate::channels::{self, ChannelDef, ChannelSource};
+use crate::config::{Config, PlaybackMode, SoundConfig};
+use crate::filter::{FilterControl, Filtered};
+use crate::flac::FlacWriter;
+use crate::looping::CrossfadeLoop;
+use crate::noise::Noise;
+use crate::pan::{PanControl, Panned};
+use crate::ramp::{Ramped, VolumeControl};
+use crate::scatter::RandomOneShots;
+
+/// Channel count of offline output
+const OFFLINE_CHANNELS: u16 = 2;
+
+/// Sample rate of offline output
+const OFFLINE_SAMPLE_RATE: u32 = 44100;
+
+/// Bytes per sample of rendered files
+const WAV_SAMPLE_BYTES: u64 = 2;
+
+/// A channel's decoded sound or generator, before the player's processing is applied
+type ChannelSound = Box<dyn Source<Item = f32> + Send>;
+
+/// Playback state of one loaded channel
+struct ChannelOutput {
+    sink: Sink,
+    volume: VolumeControl,
+    pan: PanControl,
+    filter: FilterControl,
+}
+
+/// A channel that was left out because its sound couldn't be loaded
+pub struct SkippedChannel {
+    pub id: String,
+    pub name: String,
+    pub error: anyhow::Error,
+}
+
+/// Where the mixed audio goes
+enum Output {
+    /// An audio device, played in real time
+    Device {
+        _stream: OutputStream,
+        handle: OutputStreamHandle,
+    },
+    /// Mixed only when `render` is called, so it works without a sound card.
+    /// The audio is written to a file if there is one, and discarded otherwise.
+    Offline {
+        controller: Arc<DynamicMixerController<f32>>,
+        mixer: DynamicMixer<f32>,
+        file: Option<AudioFile>,
+    },
+}
+
+/// Audio file the offline output is written to
+enum AudioFile {
+    Wav(hound::WavWriter<BufWriter<File>>),
+    Flac(FlacWriter<BufWriter<File>>),
+}
+
+impl AudioFile {
+    fn write_sample(&mut self, sample: i16) -> Result<()> {
+        match self {
+            AudioFile::Wav(wav) => wav.write_sample(sample).context("Failed to write WAV file"),
+            AudioFile::Flac(flac) => flac
+                .write_sample(sample)
+                .context("Failed to write FLAC file"),
+        }
+    }
+
+    fn finalize(self) -> Result<()> {
+        match self {
+            AudioFile::Wav(wav) => wav.finalize().context("Failed to finish WAV file"),
+            AudioFile::Flac(flac) => flac.finalize().context("Failed to finish FLAC file"),
+        }
+    }
+}
+
+impl Output {
+    fn offline(file: Option<AudioFile>) -> Self {
+        let (controller, mixer) = dynamic_mixer::mixer(OFFLINE_CHANNELS, OFFLINE_SAMPLE_RATE);
+        Output::Offline {
+            controller,
+            mixer,
+            file,
+        }
+    }
+
+    /// Create a sink that plays on this output
+    fn new_sink(&self) -> Result<Sink> {
+        match self {
+            Output::Device { handle, .. } => {
+                Sink::try_new(handle).context("Failed to create audio sink")
+            }
+            Output::Offline { controller, .. } => {
+                let (sink, queue) = Sink::new_idle();
+                controller.add(queue);
+                Ok(sink)
+            }
+        }
+    }
+}
 
 pub struct AudioPlayer {
-    _stream: OutputStream,
-    _stream_handle: OutputStreamHandle,
-    rain_sink: Sink,
-    thunder_sink: Sink,
-    campfire_sink: Sink,
+    output: Output,
+    channels: HashMap<String, ChannelOutput>,
 }
 
 impl AudioPlayer {
-    /// Create a new audio player and load all sound files
-    pub fn new() -> Result<Self> {
-        let (stream, stream_handle) =
-            OutputStream::try_default().context("Failed to create audio output stream")?;
-
-        let rain_sink = Sink::try_new(&stream_handle).context("Failed to create rain sink")?;
-        let thunder_sink =
-            Sink::try_new(&stream_handle).context("Failed to create thunder sink")?;
-        let campfire_sink =
-            Sink::try_new(&stream_handle).context("Failed to create campfire sink")?;
+    /// Create a new audio player with no channels loaded, playing on the named output device
+    /// or on the system default
+    pub fn new(device: Option<&str>) -> Result<Self> {
+        let (stream, handle) = open_stream(device)?;
 
         let player = Self {
-            _stream: stream,
-            _stream_handle: stream_handle,
-            rain_sink,
-            thunder_sink,
-            campfire_sink,
+            output: Output::Device {
+                _stream: stream,
+                handle,
+            },
+            channels: HashMap::new(),
         };
 
         Ok(player)
     }
 
-    pub fn load_sounds_and_play(&mut self) -> Result<()> {
-        // Load and start playing all sounds
-        let sounds_dir = download::get_sounds_dir()?;
-        self.load_sound(&sounds_dir.join("rain.mp3"), &self.rain_sink)?;
-        self.load_sound(&sounds_dir.join("thunder.mp3"), &self.thunder_sink)?;
-        self.load_sound(&sounds_dir.join("campfire.mp3"), &self.campfire_sink)?;
+    /// Create a player that doesn't need a sound card and throws away everything it renders
+    pub fn null() -> Self {
+        Self {
+            output: Output::offline(None),
+            channels: HashMap::new(),
+        }
+    }
+
+    /// Create a player that doesn't need a sound card and writes what it renders to a WAV file
+    pub fn wav(path: &Path) -> Result<Self> {
+        let spec = hound::WavSpec {
+            channels: OFFLINE_CHANNELS,
+            sample_rate: OFFLINE_SAMPLE_RATE,
+            bits_per_sample: (WAV_SAMPLE_BYTES * 8) as u16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let writer = hound::WavWriter::create(path, spec)
+            .context(format!("Failed to create WAV file: {}", path.display()))?;
+
+        Ok(Self {
+            output: Output::offline(Some(AudioFile::Wav(writer))),
+            channels: HashMap::new(),
+        })
+    }
 
-        // Start all sinks (they'll play at configured volumes)
-        self.rain_sink.play();
-        self.thunder_sink.play();
-        self.campfire_sink.play();
+    /// Create a player that doesn't need a sound card and writes what it renders to a FLAC file
+    pub fn flac(path: &Path) -> Result<Self> {
+        let writer = FlacWriter::create(path, OFFLINE_CHANNELS, OFFLINE_SAMPLE_RATE)
+            .context(format!("Failed to create FLAC file: {}", path.display()))?;
+
+        Ok(Self {
+            output: Output::offline(Some(AudioFile::Flac(writer))),
+            channels: HashMap::new(),
+        })
+    }
+
+    /// Mix the next `duration` of audio of an offline player.
+    /// Device output plays in real time on its own, so there is nothing to render.
+    pub fn render(&mut self, duration: Duration) -> Result<()> {
+        let Output::Offline { mixer, file, .. } = &mut self.output else {
+            return Ok(());
+        };
+
+        let frames = (duration.as_secs_f64() * OFFLINE_SAMPLE_RATE as f64) as u64;
+        let samples = frames * OFFLINE_CHANNELS as u64;
+
+        for _ in 0..samples {
+            let sample = mixer.next().unwrap_or(0.0);
+            if let Some(file) = file.as_mut() {
+                file.write_sample((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)?;
+            }
+        }
 
         Ok(())
     }
 
-    /// Load a sound file into a sink
-    fn load_sound(&self, path: &PathBuf, sink: &Sink) -> Result<()> {
-        if !path.exists() {
-            anyhow::bail!(
-                "Sound file not found: {}. Please run the app to download sounds, or check that sounds are properly installed.",
-                path.display()
+    /// Write the end of the audio file of an offline player
+    pub fn finish(&mut self) -> Result<()> {
+        if let Output::Offline { file, .. } = &mut self.output {
+            if let Some(file) = file.take() {
+                file.finalize()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Create a paused sink for each channel and load its sound file (relative to `sounds_dir`)
+    /// or generator into it. A sound file that can't be decoded only leaves out its own
+    /// channel, those channels are returned.
+    pub fn load_sounds(
+        &mut self,
+        channels: &[ChannelDef],
+        config: &Config,
+        sounds_dir: &Path,
+    ) -> Result<Vec<SkippedChannel>> {
+        let mut skipped = Vec::new();
+
+        for channel in channels {
+            let config = config.sound(&channel.id).cloned().unwrap_or_default();
+            let sound: ChannelSound = match &channel.source {
+                ChannelSource::File(file) => match load_sound(&sounds_dir.join(file), &config) {
+                    Ok(sound) => sound,
+                    Err(error) => {
+                        skipped.push(SkippedChannel {
+                            id: channel.id.clone(),
+                            name: channel.name.clone(),
+                            error,
+                        });
+                        continue;
+                    }
+                },
+                ChannelSource::Noise(color) => Box::new(Noise::new(*color)),
+            };
+
+            let sink = self
+                .output
+                .new_sink()
+                .context(format!("Failed to create {} sink", channel.name))?;
+            sink.pause();
+
+            let pan = PanControl::default();
+            pan.set(config.pan as f32 / 100.0);
+            let filter = FilterControl::default();
+            filter.set(config.low_pass_hz, config.high_pass_hz);
+
+            // Volume is applied by the ramp, so the sink itself always plays at full volume
+            let volume = VolumeControl::default();
+            let panned = Panned::new(sound, pan.clone());
+            sink.append(Ramped::new(
+                Filtered::new(panned, filter.clone()),
+                volume.clone(),
+            ));
+
+            self.channels.insert(
+                channel.id.clone(),
+                ChannelOutput {
+                    sink,
+                    volume,
+                    pan,
+                    filter,
+                },
             );
         }
 
-        let file =
-            File::open(path).context(format!("Failed to open sound file: {}", path.display()))?;
-        let source = Decoder::new(BufReader::new(file))
-            .context(format!("Failed to decode sound file: {}", path.display()))?;
+        Ok(skipped)
+    }
 
-        sink.append(source.repeat_infinite());
-        Ok(())
+    /// Move playback to another output device.
+    /// Sinks belong to the stream they were created on, so all channels are loaded again and
+    /// start paused and silent like after `load_sounds`.
+    pub fn switch_device(
+        &mut self,
+        device: Option<&str>,
+        channels: &[ChannelDef],
+        config: &Config,
+        sounds_dir: &Path,
+    ) -> Result<Vec<SkippedChannel>> {
+        let mut player = Self::new(device)?;
+        let skipped = player.load_sounds(channels, config, sounds_dir)?;
+        *self = player;
+
+        Ok(skipped)
     }
 
-    /// Update rain volume (0.0 to 1.0)
-    pub fn set_rain_volume(&self, volume: f32) {
-        self.rain_sink.set_volume(volume);
+    /// Start all sinks (they'll play at configured volumes)
+    pub fn play(&self) {
+        for output in self.channels.values() {
+            output.sink.play();
+        }
+    }
+
+    /// Pause all sinks
+    pub fn pause(&self) {
+        for output in self.channels.values() {
+            output.sink.pause();
+        }
     }
 
-    /// Update thunder volume (0.0 to 1.0)
-    pub fn set_thunder_volume(&self, volume: f32) {
-        self.thunder_sink.set_volume(volume);
+    /// Move a single channel to a new volume (0.0 to 1.0) over `ramp`
+    pub fn set_volume(&self, id: &str, volume: f32, ramp: Duration) {
+        if let Some(output) = self.channels.get(id) {
+            output.volume.set(volume, ramp);
+        }
+    }
+
+    /// Set the stereo position of a channel (-1.0 left to 1.0 right)
+    pub fn set_pan(&self, id: &str, pan: f32) {
+        if let Some(output) = self.channels.get(id) {
+            output.pan.set(pan);
+        }
+    }
+
+    /// Set the tone filter cutoffs of a channel, `None` turns a filter off
+    pub fn set_filter(&self, id: &str, low_pass_hz: Option<u32>, high_pass_hz: Option<u32>) {
+        if let Some(output) = self.channels.get(id) {
+            output.filter.set(low_pass_hz, high_pass_hz);
+        }
+    }
+
+    /// Move all channels to new volumes over `ramp`
+    pub fn update_volumes<'a>(
+        &self,
+        volumes: impl IntoIterator<Item = (&'a str, f32)>,
+        ramp: Duration,
+    ) {
+        for (id, volume) in volumes {
+            self.set_volume(id, volume, ramp);
+        }
+    }
+}
+
+/// Load a sound file using the channel's playback mode
+fn load_sound(path: &Path, sound: &SoundConfig) -> Result<ChannelSound> {
+    let source: ChannelSound = match sound.mode {
+        PlaybackMode::Loop => {
+            let crossfade = Duration::from_millis(sound.crossfade_ms as u64);
+            Box::new(CrossfadeLoop::new(decode(path)?, crossfade).convert_samples())
+        }
+        PlaybackMode::Random => {
+            let strikes = strike_files(path)?
+                .iter()
+                .map(|strike| decode(strike))
+                .collect::<Result<Vec<_>>>()?;
+
+            Box::new(
+                RandomOneShots::new(
+                    strikes,
+                    Duration::from_secs(sound.random.min_gap_secs as u64),
+                    Duration::from_secs(sound.random.max_gap_secs as u64),
+                    sound.random.intensity_variance as f32 / 100.0,
+                )
+                .convert_samples(),
+            )
+        }
+    };
+
+    Ok(source)
+}
+
+/// Get the names of all available audio output devices
+pub fn output_devices() -> Result<Vec<String>> {
+    let devices = rodio::cpal::default_host()
+        .output_devices()
+        .context("Failed to list audio output devices")?;
+
+    Ok(devices.filter_map(|device| device.name().ok()).collect())
+}
+
+/// Longest mix that fits in a WAV file, whose size is limited to 4 GiB
+pub fn max_wav_duration() -> Duration {
+    // Leave room for the header
+    let data_bytes = u32::MAX as u64 - 1024;
+    let bytes_per_sec = OFFLINE_SAMPLE_RATE as u64 * OFFLINE_CHANNELS as u64 * WAV_SAMPLE_BYTES;
+    Duration::from_secs(data_bytes / bytes_per_sec)
+}
+
+/// Get the name of the system's default output device
+pub fn default_output_device() -> Option<String> {
+    rodio::cpal::default_host()
+        .default_output_device()
+        .and_then(|device| device.name().ok())
+}
+
+/// Open an output stream on the named device, or on the default device
+fn open_stream(device: Option<&str>) -> Result<(OutputStream, OutputStreamHandle)> {
+    let Some(name) = device else {
+        return OutputStream::try_default().context("Failed to create audio output stream");
+    };
+
+    let device = rodio::cpal::default_host()
+        .output_devices()
+        .context("Failed to list audio output devices")?
+        .find(|device| device.name().is_ok_and(|device_name| device_name == name))
+        .with_context(|| format!("Audio output device '{}' not found", name))?;
+
+    OutputStream::try_from_device(&device)
+        .with_context(|| format!("Failed to open audio output device '{}'", name))
+}
+
+/// Open and decode a sound file
+fn decode(path: &Path) -> Result<Decoder<BufReader<File>>> {
+    if !path.exists() {
+        anyhow::bail!(
+            "Sound file not found: {}. Please run the app to download sounds, or check that sounds are properly installed.",
+            path.display()
+        );
     }
 
-    /// Update campfire volume (0.0 to 1.0)
-    pub fn set_campfire_volume(&self, volume: f32) {
-        self.campfire_sink.set_volume(volume);
+    let file =
+        File::open(path).context(format!("Failed to open sound file: {}", path.display()))?;
+    Decoder::new(BufReader::new(file))
+        .context(format!("Failed to decode sound file: {}", path.display()))
+}
+
+/// Find the one-shot samples for a channel played in random mode.
+/// Samples are read from a folder named after the sound file (e.g. thunder/ for thunder.mp3);
+/// without that folder the sound file itself is used as the only sample.
+fn strike_files(path: &Path) -> Result<Vec<PathBuf>> {
+    let strikes_dir = path.with_extension("");
+
+    if !strikes_dir.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
     }
 
-    /// Update all volumes from config
-    pub fn update_volumes(&self, rain_vol: f32, thunder_vol: f32, campfire_vol: f32) {
-        self.set_rain_volume(rain_vol);
-        self.set_thunder_volume(thunder_vol);
-        self.set_campfire_volume(campfire_vol);
+    let entries = fs::read_dir(&strikes_dir).context(format!(
+        "Failed to read samples directory: {}",
+        strikes_dir.display()
+    ))?;
+
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|file| file.is_file() && channels::is_sound_file(file))
+        .collect();
+    files.sort();
+
+    if files.is_empty() {
+        files.push(path.to_path_buf());
     }
+
+    Ok(files)
 }
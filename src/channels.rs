@@ -0,0 +1,177 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::noise::NoiseColor;
+use crate::soundpack::PackManifest;
+
+/// Generated noise channels as (id, display name, colour, default volume).
+/// These need no sound files, so they start silent and are always available.
+const GENERATOR_CHANNELS: &[(&str, &str, NoiseColor, u8)] = &[
+    ("white-noise", "White", NoiseColor::White, 0),
+    ("pink-noise", "Pink", NoiseColor::Pink, 0),
+    ("brown-noise", "Brown", NoiseColor::Brown, 0),
+];
+
+/// File extensions recognized as playable sounds
+const SOUND_EXTENSIONS: &[&str] = &["mp3", "ogg", "flac", "wav"];
+
+/// Default volume for custom sounds that have no config entry yet
+const CUSTOM_SOUND_VOLUME: u8 = 70;
+
+/// Definition of a single mixer channel
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChannelDef {
+    /// Stable identifier, used as the key in the config file
+    pub id: String,
+    /// Name shown in the UI
+    pub name: String,
+    /// Where the channel's audio comes from
+    pub source: ChannelSource,
+    /// Volume used when the config has no entry for this channel (0-100)
+    pub default_volume: u8,
+}
+
+/// Audio source backing a channel
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChannelSource {
+    /// Sound file, relative to the sounds directory (custom sounds use an absolute path)
+    File(PathBuf),
+    /// Procedurally generated noise
+    Noise(NoiseColor),
+}
+
+/// Ordered list of the channels the player knows about
+#[derive(Debug, Clone, Default)]
+pub struct ChannelRegistry {
+    channels: Vec<ChannelDef>,
+}
+
+impl ChannelRegistry {
+    /// Create a registry containing the channels of the sounds that come with the player
+    pub fn builtin() -> Self {
+        Self::from_packs(&[PackManifest::builtin()])
+    }
+
+    /// Create a registry with a channel for every sound of the given packs, followed by the
+    /// generated noise
+    pub fn from_packs(packs: &[PackManifest]) -> Self {
+        let files = packs.iter().flat_map(|pack| {
+            pack.sounds.iter().map(|sound| ChannelDef {
+                id: pack.channel_id(sound),
+                name: sound.name.clone(),
+                source: ChannelSource::File(pack.dir().join(&sound.file)),
+                default_volume: sound.volume,
+            })
+        });
+
+        let generators = GENERATOR_CHANNELS
+            .iter()
+            .map(|(id, name, color, default_volume)| ChannelDef {
+                id: id.to_string(),
+                name: name.to_string(),
+                source: ChannelSource::Noise(*color),
+                default_volume: *default_volume,
+            });
+
+        Self {
+            channels: files.chain(generators).collect(),
+        }
+    }
+
+    /// Add a channel to the end of the registry, replacing any channel with the same id
+    pub fn register(&mut self, channel: ChannelDef) {
+        if let Some(existing) = self.channels.iter_mut().find(|c| c.id == channel.id) {
+            *existing = channel;
+        } else {
+            self.channels.push(channel);
+        }
+    }
+
+    /// Remove file-backed channels whose sound file is missing from the sounds directory
+    pub fn retain_available(&mut self, sounds_dir: &Path) {
+        self.channels.retain(|channel| match &channel.source {
+            ChannelSource::File(file) => sounds_dir.join(file).exists(),
+            ChannelSource::Noise(_) => true,
+        });
+    }
+
+    /// Remove the channel with the given id, if there is one
+    pub fn remove(&mut self, id: &str) {
+        self.channels.retain(|channel| channel.id != id);
+    }
+
+    /// Get all channels in display order
+    pub fn channels(&self) -> &[ChannelDef] {
+        &self.channels
+    }
+
+    /// Find a channel by id or display name (case-insensitive)
+    pub fn find(&self, name: &str) -> Option<&ChannelDef> {
+        self.channels
+            .iter()
+            .find(|c| c.id.eq_ignore_ascii_case(name) || c.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Get a channel by its position in the registry
+    pub fn get(&self, index: usize) -> Option<&ChannelDef> {
+        self.channels.get(index)
+    }
+
+    /// Number of registered channels
+    pub fn len(&self) -> usize {
+        self.channels.len()
+    }
+
+    /// Check if no channels are registered
+    pub fn is_empty(&self) -> bool {
+        self.channels.is_empty()
+    }
+}
+
+/// Scan a directory for user supplied sound files and create a channel for each one.
+/// A missing directory simply means there are no custom sounds.
+pub fn custom_channels(dir: &Path) -> Result<Vec<ChannelDef>> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let entries = fs::read_dir(dir).context(format!(
+        "Failed to read custom sounds directory: {}",
+        dir.display()
+    ))?;
+
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && is_sound_file(path))
+        .collect();
+    files.sort();
+
+    let channels = files
+        .into_iter()
+        .filter_map(|path| {
+            let file_name = path.file_name()?.to_string_lossy().into_owned();
+            let name = path.file_stem()?.to_string_lossy().into_owned();
+
+            Some(ChannelDef {
+                id: format!("custom:{}", file_name),
+                name,
+                source: ChannelSource::File(path),
+                default_volume: CUSTOM_SOUND_VOLUME,
+            })
+        })
+        .collect();
+
+    Ok(channels)
+}
+
+/// Check if a file has one of the supported audio extensions
+pub fn is_sound_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            SOUND_EXTENSIONS
+                .iter()
+                .any(|supported| ext.eq_ignore_ascii_case(supported))
+        })
+}
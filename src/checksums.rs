@@ -0,0 +1,125 @@
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+/// Name of the SHA-256 manifest published next to the sounds archive
+pub const MANIFEST_NAME: &str = "sounds.sha256";
+
+/// Expected SHA-256 hashes of released files, keyed by their path in the release.
+///
+/// The manifest uses the format of `sha256sum`, one `<hex hash>  <path>` line per file, so it
+/// can be created and checked with standard tools.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Checksums {
+    hashes: BTreeMap<String, String>,
+}
+
+impl Checksums {
+    pub fn parse(contents: &str) -> Result<Self> {
+        let mut hashes = BTreeMap::new();
+
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (hash, path) = line
+                .split_once(char::is_whitespace)
+                .context(format!("Invalid checksum manifest line {}", number + 1))?;
+            // sha256sum marks files hashed in binary mode with '*'
+            let path = path.trim_start().trim_start_matches('*');
+
+            let valid_hash = hash.len() == 64 && hash.bytes().all(|b| b.is_ascii_hexdigit());
+            if !valid_hash || path.is_empty() {
+                anyhow::bail!("Invalid checksum manifest line {}", number + 1);
+            }
+
+            hashes.insert(path.to_string(), hash.to_ascii_lowercase());
+        }
+
+        if hashes.is_empty() {
+            anyhow::bail!("Checksum manifest is empty");
+        }
+
+        Ok(Self { hashes })
+    }
+
+    /// Paths of all files in the manifest
+    pub fn files(&self) -> impl Iterator<Item = &str> {
+        self.hashes.keys().map(String::as_str)
+    }
+
+    /// Check downloaded data against the manifest entry for `name`
+    pub fn verify_bytes(&self, name: &str, data: &[u8]) -> Result<()> {
+        self.check(name, &hex::encode(Sha256::digest(data)))
+    }
+
+    /// Check a file on disk against the manifest entry for `name`
+    pub fn verify_file(&self, name: &str, path: &Path) -> Result<()> {
+        let mut file = File::open(path).context(format!("Failed to open {}", path.display()))?;
+        let mut hasher = Sha256::new();
+        io::copy(&mut file, &mut hasher).context(format!("Failed to read {}", path.display()))?;
+
+        self.check(name, &hex::encode(hasher.finalize()))
+    }
+
+    fn check(&self, name: &str, actual: &str) -> Result<()> {
+        let expected = self
+            .hashes
+            .get(name)
+            .context(format!("{} is not listed in the checksum manifest", name))?;
+
+        if expected != actual {
+            anyhow::bail!(
+                "Checksum mismatch for {}, the download is corrupted or has been tampered with (expected {}, got {})",
+                name,
+                expected,
+                actual
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // SHA-256 of "hello\n"
+    const HELLO: &str = "5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03";
+
+    #[test]
+    fn parses_sha256sum_output() {
+        let manifest = format!("{}  sounds/rain.mp3\n{} *sounds.tar.gz\n\n", HELLO, HELLO);
+        let checksums = Checksums::parse(&manifest).unwrap();
+
+        assert_eq!(
+            checksums.files().collect::<Vec<_>>(),
+            ["sounds.tar.gz", "sounds/rain.mp3"]
+        );
+        assert!(Checksums::parse("").is_err());
+        assert!(Checksums::parse("abc  sounds/rain.mp3").is_err());
+        assert!(Checksums::parse(HELLO).is_err());
+    }
+
+    #[test]
+    fn rejects_modified_data() {
+        let checksums = Checksums::parse(&format!("{}  sounds.tar.gz", HELLO)).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sounds.tar.gz");
+
+        std::fs::write(&path, b"hello\n").unwrap();
+        checksums.verify_file("sounds.tar.gz", &path).unwrap();
+
+        std::fs::write(&path, b"hello!\n").unwrap();
+        let error = checksums.verify_file("sounds.tar.gz", &path).unwrap_err();
+        assert!(error.to_string().contains("Checksum mismatch"));
+
+        assert!(checksums.verify_file("sounds/rain.mp3", &path).is_err());
+    }
+}
@@ -0,0 +1,401 @@
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::app::Session;
+#[cfg(unix)]
+use crate::control::{self, Command, Status};
+#[cfg(unix)]
+use crate::filter;
+#[cfg(unix)]
+use crate::pan;
+use crate::sleep;
+
+/// A terminal-based relaxation sound player with ambient sounds like rain, thunder, and campfire
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Play the saved mix without the terminal UI (stop with Ctrl+C)
+    #[arg(long)]
+    pub headless: bool,
+
+    /// Set a channel's volume for this session, e.g. --volume rain=40
+    #[arg(long = "volume", value_name = "CHANNEL=VOLUME", value_parser = parse_channel_volume)]
+    pub volumes: Vec<(String, u8)>,
+
+    /// Mute a channel for this session
+    #[arg(long = "mute", value_name = "CHANNEL")]
+    pub muted: Vec<String>,
+
+    /// Set the master volume for this session (0-100)
+    #[arg(long, value_name = "VOLUME", value_parser = clap::value_parser!(u8).range(0..=100))]
+    pub master: Option<u8>,
+
+    /// Start with a saved preset, e.g. --preset "Heavy storm"
+    #[arg(long, value_name = "NAME")]
+    pub preset: Option<String>,
+
+    /// Play on this audio output device (see `relax-player devices`)
+    #[arg(long, value_name = "NAME")]
+    pub device: Option<String>,
+
+    /// Stop playing after this long, e.g. 45m, 1h30m or 90s (starts the sleep timer)
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub duration: Option<Duration>,
+
+    #[command(subcommand)]
+    pub command: Option<CliCommand>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CliCommand {
+    /// List the available audio output devices
+    Devices,
+    /// Play in the background and accept commands from `relax-player ctl` and the TUI
+    Daemon,
+    /// Save the mix to a WAV or FLAC file, e.g. render --preset Storm --duration 2h --out storm.flac
+    Render {
+        /// Render a saved preset instead of the current mix
+        #[arg(long, value_name = "NAME")]
+        preset: Option<String>,
+
+        /// Length of the recording, e.g. 45m, 1h30m or 90s
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+        duration: Duration,
+
+        /// WAV or FLAC file to write, chosen by the extension
+        #[arg(long, value_name = "FILE")]
+        out: PathBuf,
+    },
+    /// Install sounds from a sounds.tar.gz or a folder, for machines that can't reach GitHub
+    InstallSounds {
+        /// Archive or folder with the sound files
+        path: PathBuf,
+    },
+    /// List, install or remove sound packs
+    Packs {
+        #[command(subcommand)]
+        action: PacksAction,
+    },
+    /// Control a running daemon
+    Ctl {
+        /// Print the raw JSON status instead of a summary
+        #[arg(long)]
+        json: bool,
+
+        #[command(subcommand)]
+        action: CtlAction,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum PacksAction {
+    /// Show the installed packs and their sounds
+    List,
+    /// Install or update a pack from a URL, a pack archive or a folder
+    Install {
+        /// URL serving the pack's sounds.tar.gz, sounds.sha256 and sounds.json, or a local
+        /// archive or folder with the pack's sounds.json next to the sounds
+        source: String,
+    },
+    /// Remove an installed pack
+    Remove {
+        /// Id of the pack, as shown by `packs list`
+        id: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CtlAction {
+    /// Show volumes, mute states and the sleep timer
+    Status,
+    /// Set a channel's volume (use "master" for the master volume)
+    Volume { channel: String, volume: u8 },
+    /// Mute a channel
+    Mute { channel: String },
+    /// Unmute a channel
+    Unmute { channel: String },
+    /// Toggle mute for a channel
+    ToggleMute { channel: String },
+    /// Set a channel's stereo position from -100 (left) to 100 (right)
+    Pan {
+        channel: String,
+        #[arg(allow_negative_numbers = true, value_parser = clap::value_parser!(i8).range(-100..=100))]
+        pan: i8,
+    },
+    /// Set a channel's low-pass cutoff in Hz (e.g. 2000), or turn it off with "off"
+    LowPass { channel: String, cutoff: String },
+    /// Set a channel's high-pass cutoff in Hz (e.g. 200), or turn it off with "off"
+    HighPass { channel: String, cutoff: String },
+    /// Pause playback
+    Pause,
+    /// Resume playback
+    Resume,
+    /// Start the sleep timer (e.g. 30m), or cancel it with "off"
+    Sleep { duration: String },
+    /// Switch to a saved preset
+    Preset { name: String },
+    /// Save the current mix as a preset
+    SavePreset { name: String },
+    /// Delete a preset
+    DeletePreset { name: String },
+    /// Move playback to another output device, or back to the system default with "default"
+    Device { name: String },
+    /// Stop the daemon
+    Stop,
+}
+
+#[cfg(unix)]
+impl CtlAction {
+    /// Translate into the command sent to the daemon
+    pub fn into_command(self) -> Result<Command, String> {
+        let command = match self {
+            CtlAction::Status => Command::Status,
+            CtlAction::Volume { channel, volume } => Command::SetVolume { channel, volume },
+            CtlAction::Mute { channel } => Command::SetMute {
+                channel,
+                muted: true,
+            },
+            CtlAction::Unmute { channel } => Command::SetMute {
+                channel,
+                muted: false,
+            },
+            CtlAction::ToggleMute { channel } => Command::ToggleMute { channel },
+            CtlAction::Pan { channel, pan } => Command::SetPan { channel, pan },
+            CtlAction::LowPass { channel, cutoff } => Command::SetLowPass {
+                channel,
+                hz: parse_cutoff(&cutoff)?,
+            },
+            CtlAction::HighPass { channel, cutoff } => Command::SetHighPass {
+                channel,
+                hz: parse_cutoff(&cutoff)?,
+            },
+            CtlAction::Pause => Command::SetPaused { paused: true },
+            CtlAction::Resume => Command::SetPaused { paused: false },
+            CtlAction::Sleep { duration } => {
+                let seconds = if duration.eq_ignore_ascii_case("off") {
+                    None
+                } else {
+                    Some(parse_duration(&duration)?.as_secs())
+                };
+                Command::SetSleepTimer { seconds }
+            }
+            CtlAction::Preset { name } => Command::LoadPreset { name },
+            CtlAction::SavePreset { name } => Command::SavePreset { name },
+            CtlAction::DeletePreset { name } => Command::DeletePreset { name },
+            CtlAction::Device { name } => Command::SetDevice {
+                name: (!name.eq_ignore_ascii_case("default")).then_some(name),
+            },
+            CtlAction::Stop => Command::Stop,
+        };
+
+        Ok(command)
+    }
+}
+
+/// Print a status report in a human readable form
+#[cfg(unix)]
+pub fn print_status(status: &Status) {
+    let width = status
+        .channels
+        .iter()
+        .map(|channel| channel.name.len())
+        .max()
+        .unwrap_or(0)
+        .max(control::MASTER.len());
+
+    for channel in status
+        .channels
+        .iter()
+        .chain(std::iter::once(&status.master))
+    {
+        let muted = if channel.muted { " (muted)" } else { "" };
+        let mut effects = String::new();
+        if channel.pan != 0 {
+            effects += &format!(" pan {}", pan::format_pan(channel.pan));
+        }
+        if let Some(hz) = channel.low_pass_hz {
+            effects += &format!(" low-pass {}", filter::format_cutoff(hz));
+        }
+        if let Some(hz) = channel.high_pass_hz {
+            effects += &format!(" high-pass {}", filter::format_cutoff(hz));
+        }
+        println!(
+            "{:<width$}  {:>3}%{}{}",
+            channel.name,
+            channel.volume,
+            effects,
+            muted,
+            width = width
+        );
+    }
+
+    if let Some(device) = &status.device {
+        println!("Output: {}", device);
+    }
+    if let Some(preset) = &status.preset {
+        println!("Preset: {}", preset);
+    }
+    if status.paused {
+        println!("Paused");
+    }
+    if let Some(timer) = &status.sleep_timer {
+        println!(
+            "Sleep in {}",
+            sleep::format_remaining(Duration::from_secs(timer.remaining_secs))
+        );
+    }
+}
+
+impl Cli {
+    /// Settings from the command line that only apply to this run
+    pub fn session(&self) -> Session {
+        Session {
+            volumes: self.volumes.clone(),
+            muted: self.muted.clone(),
+            master: self.master,
+            preset: self.preset.clone(),
+            device: self.device.clone(),
+            duration: self.duration,
+        }
+    }
+}
+
+/// Parse a CHANNEL=VOLUME pair
+fn parse_channel_volume(value: &str) -> Result<(String, u8), String> {
+    let (channel, volume) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected CHANNEL=VOLUME, got '{}'", value))?;
+
+    let volume: u8 = volume
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid volume '{}', expected 0-100", volume))?;
+    if volume > 100 {
+        return Err(format!("invalid volume '{}', expected 0-100", volume));
+    }
+
+    Ok((channel.trim().to_string(), volume))
+}
+
+/// Parse a duration made of hour/minute/second parts, e.g. 2h, 45m, 1h30m or 90s.
+/// A bare number is taken as minutes. Durations are limited to `sleep::MAX_DURATION`.
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let too_long = || {
+        format!(
+            "duration '{}' is too long, at most {} is allowed",
+            value,
+            sleep::format_remaining(sleep::MAX_DURATION)
+        )
+    };
+    let limit = |secs: u64| {
+        let duration = Duration::from_secs(secs);
+        if duration > sleep::MAX_DURATION {
+            Err(too_long())
+        } else {
+            Ok(duration)
+        }
+    };
+
+    if value.bytes().all(|b| b.is_ascii_digit()) && !value.is_empty() {
+        let minutes: u64 = value.parse().map_err(|_| too_long())?;
+        if minutes == 0 {
+            return Err(invalid_duration(value));
+        }
+        return limit(minutes.checked_mul(60).ok_or_else(too_long)?);
+    }
+
+    let mut total: u64 = 0;
+    let mut number = String::new();
+
+    for c in value.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+
+        let unit = match c {
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return Err(invalid_duration(value)),
+        };
+        if number.is_empty() {
+            return Err(invalid_duration(value));
+        }
+        let amount: u64 = number.parse().map_err(|_| too_long())?;
+        total = amount
+            .checked_mul(unit)
+            .and_then(|secs| total.checked_add(secs))
+            .ok_or_else(too_long)?;
+        number.clear();
+    }
+
+    if !number.is_empty() || total == 0 {
+        return Err(invalid_duration(value));
+    }
+
+    limit(total)
+}
+
+/// Parse a filter cutoff in Hz, "off" turns the filter off
+#[cfg(unix)]
+fn parse_cutoff(value: &str) -> Result<Option<u32>, String> {
+    if value.eq_ignore_ascii_case("off") {
+        return Ok(None);
+    }
+
+    value
+        .trim()
+        .parse()
+        .map(Some)
+        .map_err(|_| format!("invalid cutoff '{}', expected e.g. 2000 or off", value))
+}
+
+fn invalid_duration(value: &str) -> String {
+    format!("invalid duration '{}', use e.g. 45m or 1h30m", value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("45"), Ok(Duration::from_secs(45 * 60)));
+        assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(2 * 3600)));
+        assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(5400)));
+        assert_eq!(parse_duration(" 90s "), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("24h"), Ok(sleep::MAX_DURATION));
+    }
+
+    #[test]
+    fn rejects_invalid_durations() {
+        for value in ["", "0", "0m", "h", "1x", "1h30", "m30", "-5m"] {
+            assert!(
+                parse_duration(value).unwrap_err().contains("invalid"),
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_overlong_durations() {
+        for value in [
+            "1441",
+            "24h1s",
+            "18446744073709551615",
+            "99999999999999999999",
+            "18446744073709551615h",
+            "5124095576030431h5124095576030431h",
+        ] {
+            assert!(
+                parse_duration(value).unwrap_err().contains("too long"),
+                "{}",
+                value
+            );
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use serde::{Deserialize, Serialize};
+
+/// Channel name used to address the master volume
+pub const MASTER: &str = "master";
+
+/// Actions that change the player state.
+/// The TUI applies these to its own player, or sends them as JSON to a running daemon.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Command {
+    /// Only report the current state
+    Status,
+    SetVolume {
+        channel: String,
+        volume: u8,
+    },
+    /// Change a volume relative to its current value
+    AdjustVolume {
+        channel: String,
+        delta: i16,
+    },
+    SetMute {
+        channel: String,
+        muted: bool,
+    },
+    ToggleMute {
+        channel: String,
+    },
+    /// Set a channel's stereo position from -100 (left) to 100 (right)
+    SetPan {
+        channel: String,
+        pan: i8,
+    },
+    /// Move a channel's stereo position relative to its current value
+    AdjustPan {
+        channel: String,
+        delta: i16,
+    },
+    /// Set a channel's low-pass cutoff in Hz, or turn the filter off with `None`
+    SetLowPass {
+        channel: String,
+        hz: Option<u32>,
+    },
+    /// Set a channel's high-pass cutoff in Hz, or turn the filter off with `None`
+    SetHighPass {
+        channel: String,
+        hz: Option<u32>,
+    },
+    SetPaused {
+        paused: bool,
+    },
+    /// Start the sleep timer, or cancel it with `None`
+    SetSleepTimer {
+        seconds: Option<u64>,
+    },
+    /// Switch to a saved mix
+    LoadPreset {
+        name: String,
+    },
+    /// Save the current mix, replacing any preset with the same name
+    SavePreset {
+        name: String,
+    },
+    DeletePreset {
+        name: String,
+    },
+    /// Move playback to another output device, or to the system default with `None`
+    SetDevice {
+        name: Option<String>,
+    },
+    /// Stop playback and exit
+    Stop,
+}
+
+/// Volume and mute state of one channel
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChannelStatus {
+    pub id: String,
+    pub name: String,
+    pub volume: u8,
+    pub muted: bool,
+    pub pan: i8,
+    pub low_pass_hz: Option<u32>,
+    pub high_pass_hz: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SleepStatus {
+    /// Length the timer was set to
+    pub duration_secs: u64,
+    pub remaining_secs: u64,
+}
+
+/// Snapshot of the player state, returned after every command
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Status {
+    pub channels: Vec<ChannelStatus>,
+    pub master: ChannelStatus,
+    pub paused: bool,
+    pub sleep_timer: Option<SleepStatus>,
+    /// Names of all saved presets
+    pub presets: Vec<String>,
+    /// The last preset that was loaded or saved
+    pub preset: Option<String>,
+    /// Output device, `None` for the system default
+    pub device: Option<String>,
+}
+
+/// Reply to a command, sent back over the daemon's control socket
+#[cfg(unix)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum Response {
+    Ok { status: Status },
+    Error { message: String },
+}
+
+#[cfg(unix)]
+impl From<anyhow::Result<Status>> for Response {
+    fn from(result: anyhow::Result<Status>) -> Self {
+        match result {
+            Ok(status) => Response::Ok { status },
+            Err(error) => Response::Error {
+                message: format!("{:#}", error),
+            },
+        }
+    }
+}
@@ -0,0 +1,346 @@
+use anyhow::{Context, Result};
+use directories::ProjectDirs;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+use crate::control::{Command, Response, Status};
+
+/// Get the path of the daemon control socket.
+/// Uses the user's runtime directory where available, otherwise the data directory.
+pub fn socket_path() -> Result<PathBuf> {
+    let proj_dirs = ProjectDirs::from("com", "relax-player", "relax-player")
+        .context("Failed to determine runtime directory")?;
+
+    let dir = proj_dirs.runtime_dir().unwrap_or(proj_dirs.data_dir());
+    fs::create_dir_all(dir).context("Failed to create runtime directory")?;
+
+    Ok(dir.join("relax-player.sock"))
+}
+
+/// Connection to a running daemon
+pub struct Client {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+}
+
+impl Client {
+    /// Connect to the daemon, failing if none is running
+    pub fn connect() -> Result<Self> {
+        Self::connect_to(&socket_path()?)
+    }
+
+    /// Connect to a daemon listening on `path`
+    fn connect_to(path: &Path) -> Result<Self> {
+        let stream = UnixStream::connect(path).context(format!(
+            "Failed to connect to daemon at {}. Start it with `relax-player daemon`.",
+            path.display()
+        ))?;
+
+        let writer = stream
+            .try_clone()
+            .context("Failed to set up daemon connection")?;
+
+        Ok(Self {
+            reader: BufReader::new(stream),
+            writer,
+        })
+    }
+
+    /// Connect to the daemon if one is running
+    pub fn try_connect() -> Option<Self> {
+        Self::connect().ok()
+    }
+
+    /// Send a command and wait for the resulting status
+    pub fn send(&mut self, command: &Command) -> Result<Status> {
+        let mut line = serde_json::to_string(command).context("Failed to serialize command")?;
+        line.push('\n');
+        self.writer
+            .write_all(line.as_bytes())
+            .context("Failed to send command to daemon")?;
+
+        let mut reply = String::new();
+        let read = self
+            .reader
+            .read_line(&mut reply)
+            .context("Failed to read reply from daemon")?;
+        if read == 0 {
+            anyhow::bail!("Daemon closed the connection");
+        }
+
+        match serde_json::from_str(&reply).context("Failed to parse reply from daemon")? {
+            Response::Ok { status } => Ok(status),
+            Response::Error { message } => anyhow::bail!(message),
+        }
+    }
+}
+
+/// A command received from a client, waiting for its reply
+pub struct Request {
+    pub command: Command,
+    reply: Sender<Response>,
+}
+
+impl Request {
+    pub fn reply(self, response: Response) {
+        // The client may have disconnected in the meantime, which is fine
+        let _ = self.reply.send(response);
+    }
+}
+
+/// Listens on the control socket and hands commands to the thread that owns the player
+pub struct Server {
+    path: PathBuf,
+    requests: Receiver<Request>,
+}
+
+impl Server {
+    /// Bind the control socket, replacing a stale socket left behind by a previous daemon
+    pub fn bind() -> Result<Self> {
+        Self::bind_to(socket_path()?)
+    }
+
+    /// Bind the control socket at `path`
+    fn bind_to(path: PathBuf) -> Result<Self> {
+        if path.exists() {
+            if UnixStream::connect(&path).is_ok() {
+                anyhow::bail!("A daemon is already running at {}", path.display());
+            }
+            fs::remove_file(&path).context("Failed to remove stale daemon socket")?;
+        }
+
+        let listener = UnixListener::bind(&path)
+            .context(format!("Failed to bind daemon socket: {}", path.display()))?;
+
+        let (sender, requests) = mpsc::channel();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let sender = sender.clone();
+                thread::spawn(move || handle_client(stream, sender));
+            }
+        });
+
+        Ok(Self { path, requests })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Wait up to `timeout` for the next command from any client
+    pub fn next_request(&self, timeout: Duration) -> Option<Request> {
+        self.requests.recv_timeout(timeout).ok()
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Read newline-delimited JSON commands from a client and write one reply line for each
+fn handle_client(stream: UnixStream, requests: Sender<Request>) {
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            return;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match serde_json::from_str::<Command>(&line) {
+            Ok(command) => {
+                let (reply, response) = mpsc::channel();
+                if requests.send(Request { command, reply }).is_err() {
+                    return;
+                }
+                match response.recv() {
+                    Ok(response) => response,
+                    Err(_) => return,
+                }
+            }
+            Err(error) => Response::Error {
+                message: format!("Invalid command: {}", error),
+            },
+        };
+
+        let Ok(mut reply) = serde_json::to_string(&response) else {
+            return;
+        };
+        reply.push('\n');
+        if writer.write_all(reply.as_bytes()).is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::ChannelStatus;
+
+    fn status(paused: bool) -> Status {
+        Status {
+            channels: Vec::new(),
+            master: ChannelStatus {
+                id: "master".to_string(),
+                name: "Master".to_string(),
+                volume: 100,
+                muted: false,
+                pan: 0,
+                low_pass_hz: None,
+                high_pass_hz: None,
+            },
+            paused,
+            sleep_timer: None,
+            presets: Vec::new(),
+            preset: None,
+            device: None,
+        }
+    }
+
+    /// A socket path of its own for each test, which the server removes again when dropped
+    fn test_socket(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "relax-player-test-{}-{}.sock",
+            std::process::id(),
+            name
+        ));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    /// Answer commands like a tiny player that only knows pausing, until a `Stop`
+    fn serve(server: Server) -> thread::JoinHandle<()> {
+        thread::spawn(move || {
+            let mut paused = false;
+            while let Some(request) = server.next_request(Duration::from_secs(10)) {
+                let response = match &request.command {
+                    Command::SetPaused { paused: value } => {
+                        paused = *value;
+                        Response::Ok {
+                            status: status(paused),
+                        }
+                    }
+                    Command::SetVolume { channel, .. } => Response::Error {
+                        message: format!("Unknown channel '{}'", channel),
+                    },
+                    _ => Response::Ok {
+                        status: status(paused),
+                    },
+                };
+                let stop = matches!(request.command, Command::Stop);
+                request.reply(response);
+                if stop {
+                    return;
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn client_gets_status_and_errors() {
+        let path = test_socket("status");
+        let player = serve(Server::bind_to(path.clone()).unwrap());
+
+        let mut client = Client::connect_to(&path).unwrap();
+        assert!(!client.send(&Command::Status).unwrap().paused);
+        assert!(
+            client
+                .send(&Command::SetPaused { paused: true })
+                .unwrap()
+                .paused
+        );
+        assert!(client.send(&Command::Status).unwrap().paused);
+
+        let error = client
+            .send(&Command::SetVolume {
+                channel: "missing".to_string(),
+                volume: 50,
+            })
+            .unwrap_err();
+        assert_eq!(error.to_string(), "Unknown channel 'missing'");
+
+        client.send(&Command::Stop).unwrap();
+        player.join().unwrap();
+    }
+
+    #[test]
+    fn garbage_gets_an_error_and_keeps_the_server_running() {
+        let path = test_socket("garbage");
+        let player = serve(Server::bind_to(path.clone()).unwrap());
+
+        let mut stream = UnixStream::connect(&path).unwrap();
+        stream.write_all(b"not a command\n").unwrap();
+        let mut reply = String::new();
+        BufReader::new(&mut stream).read_line(&mut reply).unwrap();
+        match serde_json::from_str(&reply).unwrap() {
+            Response::Error { message } => assert!(message.starts_with("Invalid command")),
+            Response::Ok { .. } => panic!("garbage was accepted"),
+        }
+
+        let mut client = Client::connect_to(&path).unwrap();
+        assert!(!client.send(&Command::Status).unwrap().paused);
+        client.send(&Command::Stop).unwrap();
+        player.join().unwrap();
+    }
+
+    #[test]
+    fn replaces_stale_socket() {
+        let path = test_socket("stale");
+
+        // A listener that is dropped leaves its socket file behind, like a daemon that crashed
+        drop(UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+
+        let server = Server::bind_to(path.clone()).unwrap();
+        let error = Server::bind_to(path.clone()).err().unwrap();
+        assert!(error.to_string().contains("already running"));
+
+        let player = serve(server);
+        let mut client = Client::connect_to(&path).unwrap();
+        client.send(&Command::Stop).unwrap();
+        player.join().unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn serves_several_clients() {
+        let path = test_socket("clients");
+        let player = serve(Server::bind_to(path.clone()).unwrap());
+
+        let senders: Vec<_> = (0..4)
+            .map(|_| {
+                let mut client = Client::connect_to(&path).unwrap();
+                thread::spawn(move || {
+                    for _ in 0..20 {
+                        client.send(&Command::Status).unwrap();
+                    }
+                    client
+                })
+            })
+            .collect();
+        let mut clients: Vec<_> = senders.into_iter().map(|s| s.join().unwrap()).collect();
+
+        // Every connection still works, and sees changes made through another one
+        clients[0]
+            .send(&Command::SetPaused { paused: true })
+            .unwrap();
+        for client in &mut clients[1..] {
+            assert!(client.send(&Command::Status).unwrap().paused);
+        }
+
+        clients[0].send(&Command::Stop).unwrap();
+        player.join().unwrap();
+    }
+}
@@ -1,144 +1,983 @@
 use anyhow::{Context, Result};
-use directories::ProjectDirs;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
 use flate2::read::GzDecoder;
 use ratatui::DefaultTerminal;
+use reqwest::StatusCode;
+use std::fmt;
 use std::fs;
+use std::io::{BufReader, Write};
 use std::path::{Path, PathBuf};
-use tar::Archive;
+use std::time::{Duration, Instant};
 
-use crate::config::Config;
-use crate::prompt::{run_prompt, PromptType};
+use crate::checksums::{self, Checksums};
+use crate::config::{Config, DownloadConfig};
+use crate::extract;
+use crate::http::{self, FailureKind, HttpClient, HttpStatusError, RetryPolicy};
+use crate::prompt::{self, run_prompt, PromptType};
+use crate::sleep;
+use crate::soundpack::{self, PackManifest};
 
-const REQUIRED_SOUNDS: &[&str] = &["rain.mp3", "thunder.mp3", "campfire.mp3"];
+/// Sounds directory in the CWD, which debug builds use if the sounds are there
+const CWD_SOUNDS_DIR: &str = "sounds";
 
 /// Check if all required sound files exist in the CWD's sounds/ directory
-pub fn check_cwd_sounds() -> bool {
-    let cwd_sounds = Path::new("sounds");
+fn check_cwd_sounds() -> bool {
+    let cwd_sounds = Path::new(CWD_SOUNDS_DIR);
 
     if !cwd_sounds.exists() || !cwd_sounds.is_dir() {
         return false;
     }
 
-    for sound in REQUIRED_SOUNDS {
-        let sound_path = cwd_sounds.join(sound);
-        if !sound_path.exists() {
-            return false;
-        }
-    }
-
-    true
+    PackManifest::builtin().missing_files(cwd_sounds).is_empty()
 }
 
-/// Get the sounds directory path
+/// Get the sounds directory path, use `Dirs::locate` rather than calling this directly
 /// In debug mode: Check CWD first, fall back to data directory
 /// In release mode: Use data directory only
 pub fn get_sounds_dir() -> Result<PathBuf> {
     if cfg!(debug_assertions) && check_cwd_sounds() {
-        Ok(PathBuf::from("sounds"))
+        Ok(PathBuf::from(CWD_SOUNDS_DIR))
     } else {
         Config::sounds_dir()
     }
 }
 
-/// Check if all required sound files exist in the sounds directory
-/// In debug mode: Checks CWD first, then data directory
-/// In release mode: Only checks data directory
-pub fn sounds_exist() -> Result<bool> {
-    let sounds_dir = get_sounds_dir()?;
+/// Check if `sounds_dir` is the CWD's sounds/ directory of a debug build, which is never
+/// downloaded into
+pub fn is_cwd_sounds(sounds_dir: &Path) -> bool {
+    cfg!(debug_assertions) && sounds_dir == Path::new(CWD_SOUNDS_DIR)
+}
+
+/// Check if all sound files of the default pack exist in the sounds directory
+pub fn sounds_exist(sounds_dir: &Path) -> bool {
+    soundpack::default_pack(sounds_dir)
+        .missing_files(sounds_dir)
+        .is_empty()
+}
+
+/// Error returned when the user cancels a download
+#[derive(Debug)]
+pub struct DownloadCancelled;
+
+impl fmt::Display for DownloadCancelled {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Download cancelled")
+    }
+}
+
+impl std::error::Error for DownloadCancelled {}
 
-    for sound in REQUIRED_SOUNDS {
-        let sound_path = sounds_dir.join(sound);
-        if !sound_path.exists() {
-            return Ok(false);
+/// Error returned when every download source failed, listing what went wrong with each
+#[derive(Debug)]
+pub struct SourcesFailed {
+    failures: Vec<(String, anyhow::Error)>,
+}
+
+impl SourcesFailed {
+    /// Kind of the first request failure, which the flattened list of errors doesn't carry
+    fn failure_kind(&self) -> Option<FailureKind> {
+        self.failures
+            .iter()
+            .find_map(|(_, error)| http::failure_kind(error))
+    }
+}
+
+impl fmt::Display for SourcesFailed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Failed to download sounds from any source:")?;
+        for (source, error) in &self.failures {
+            write!(f, "\n{}: {:#}", source, error)?;
         }
+        Ok(())
     }
+}
+
+impl std::error::Error for SourcesFailed {}
 
-    Ok(true)
+/// How far a file download has got
+#[derive(Debug, Clone)]
+pub struct DownloadProgress {
+    /// Name of the file being downloaded
+    pub name: String,
+    pub received: u64,
+    /// Size of the file, if the server reported it
+    pub total: Option<u64>,
+    /// Bytes that were already there when a download was resumed
+    resumed: u64,
+    started: Instant,
 }
 
-/// Download and extract sounds from GitHub release
+impl DownloadProgress {
+    pub fn new(name: &str, total: Option<u64>) -> Self {
+        Self {
+            name: name.to_string(),
+            received: 0,
+            total,
+            resumed: 0,
+            started: Instant::now(),
+        }
+    }
+
+    /// Continue a download that already has `received` bytes
+    pub fn resume_from(&mut self, received: u64) {
+        self.received = received;
+        self.resumed = received;
+    }
+
+    /// Fraction of the file received, if the size is known
+    pub fn ratio(&self) -> Option<f64> {
+        self.total
+            .filter(|total| *total > 0)
+            .map(|total| (self.received as f64 / total as f64).min(1.0))
+    }
+
+    /// Average download speed in bytes per second
+    pub fn speed(&self) -> f64 {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        if elapsed > 0.0 {
+            (self.received - self.resumed) as f64 / elapsed
+        } else {
+            0.0
+        }
+    }
+
+    /// Estimated time until the download is complete
+    pub fn eta(&self) -> Option<Duration> {
+        let remaining = self.total?.saturating_sub(self.received);
+        let speed = self.speed();
+        (speed > 0.0).then(|| Duration::from_secs_f64(remaining as f64 / speed))
+    }
+
+    /// Describe the progress in one line, e.g. "4.2 MB / 10.0 MB, 1.1 MB/s, 00:05 left"
+    pub fn summary(&self) -> String {
+        let mut summary = format_bytes(self.received);
+        if let Some(total) = self.total {
+            summary += &format!(" / {}", format_bytes(total));
+        }
+        summary += &format!(", {}/s", format_bytes(self.speed() as u64));
+        if let Some(eta) = self.eta() {
+            summary += &format!(", {} left", sleep::format_remaining(eta));
+        }
+        summary
+    }
+}
+
+/// Describe a size in bytes for display, e.g. "512 KB" or "4.2 MB"
+pub fn format_bytes(bytes: u64) -> String {
+    const KB: u64 = 1024;
+    const MB: u64 = 1024 * KB;
+
+    if bytes >= MB {
+        format!("{:.1} MB", bytes as f64 / MB as f64)
+    } else if bytes >= KB {
+        format!("{} KB", bytes / KB)
+    } else {
+        format!("{} B", bytes)
+    }
+}
+
+/// Receives status messages while sounds are downloaded
+pub trait DownloadReporter {
+    fn report(&mut self, title: &str, message: &str) -> Result<()>;
+
+    /// Called regularly while a file is downloading. Returning an error stops the download.
+    fn progress(&mut self, progress: &DownloadProgress) -> Result<()>;
+
+    /// Called regularly while waiting to retry. Returning an error stops the download.
+    fn check_cancelled(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Show download status as prompts in the TUI, Esc cancels a running download
+impl DownloadReporter for DefaultTerminal {
+    fn report(&mut self, title: &str, message: &str) -> Result<()> {
+        run_prompt(self, title, message, PromptType::Info)?;
+        Ok(())
+    }
+
+    fn progress(&mut self, progress: &DownloadProgress) -> Result<()> {
+        let label = match progress.ratio() {
+            Some(ratio) => format!("{:.0}%", ratio * 100.0),
+            None => format_bytes(progress.received),
+        };
+        prompt::draw_progress(
+            self,
+            &format!("Downloading {}", progress.name),
+            progress.ratio().unwrap_or(0.0),
+            &label,
+            &progress.summary(),
+        )?;
+        self.check_cancelled()
+    }
+
+    fn check_cancelled(&mut self) -> Result<()> {
+        while event::poll(Duration::ZERO)? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press && key.code == KeyCode::Esc {
+                    return Err(DownloadCancelled.into());
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Print download status to stderr, for running without the TUI
+#[derive(Debug, Default)]
+pub struct ConsoleReporter {
+    /// Whether a progress line is shown that the next message has to start below
+    showing_progress: bool,
+}
+
+impl DownloadReporter for ConsoleReporter {
+    fn report(&mut self, title: &str, message: &str) -> Result<()> {
+        if std::mem::take(&mut self.showing_progress) {
+            eprintln!();
+        }
+        eprintln!("{}: {}", title, message);
+        Ok(())
+    }
+
+    fn progress(&mut self, progress: &DownloadProgress) -> Result<()> {
+        let percent = progress
+            .ratio()
+            .map(|ratio| format!("{:>3.0}% ", ratio * 100.0))
+            .unwrap_or_default();
+        // Pad to clear what's left of a longer previous line
+        eprint!("\r{}{:<60}", percent, progress.summary());
+        std::io::stderr().flush()?;
+        self.showing_progress = true;
+        Ok(())
+    }
+}
+
+/// Name of the sounds archive attached to each release
+const ARCHIVE_NAME: &str = "sounds.tar.gz";
+
+/// Directory next to the sounds directory that downloads are extracted to before being verified
+const STAGING_DIR: &str = "download";
+
+/// Where the archive of `pack` is downloaded to, next to the sounds directory. It's kept until
+/// the download is complete, so a later run can resume it.
+fn partial_download(sounds_dir: &Path, pack: &PackManifest) -> Result<PathBuf> {
+    let part = sounds_dir.with_file_name(format!("{}-{}.tar.gz.part", pack.id, pack.version));
+    if let Some(parent) = part
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent).context("Failed to create data directory")?;
+    }
+    Ok(part)
+}
+
+/// Point out the likely cause when a release file doesn't exist
+fn release_hint(error: anyhow::Error) -> anyhow::Error {
+    match error.downcast_ref::<HttpStatusError>() {
+        Some(status) if status.status == StatusCode::NOT_FOUND => {
+            let hint = format!(
+                "Make sure the release exists with {}, {} and {} attached",
+                ARCHIVE_NAME,
+                checksums::MANIFEST_NAME,
+                soundpack::MANIFEST_NAME
+            );
+            error.context(hint)
+        }
+        _ => error,
+    }
+}
+
+/// Title and advice for a failed sound download, depending on what went wrong. There's no
+/// advice when the download worked but the sounds were wrong, e.g. for a checksum mismatch.
+pub fn failure_help(error: &anyhow::Error) -> (&'static str, Option<&'static str>) {
+    let kind = match error.downcast_ref::<SourcesFailed>() {
+        Some(failed) => failed.failure_kind(),
+        None => http::failure_kind(error),
+    };
+    match kind {
+        Some(FailureKind::Tls) => (
+            "TLS Error",
+            Some("The secure connection failed. If your network uses its own certificate authority, set download.ca_cert in the config file to its PEM file."),
+        ),
+        Some(FailureKind::Proxy) => (
+            "Proxy Error",
+            Some("Check the proxy settings, download.proxy in the config file or the HTTPS_PROXY environment variable."),
+        ),
+        Some(FailureKind::Http) => (
+            "HTTP Error",
+            Some("The download server answered with an error. Check the download URL in the config file."),
+        ),
+        Some(FailureKind::Network) => ("Error", Some("Check your internet connection.")),
+        None => ("Error", None),
+    }
+}
+
+/// Download and extract sounds, trying each configured source in order until one works (see
+/// `DownloadConfig::sources`).
+/// The archive and every extracted file are checked against the release's SHA-256 manifest,
+/// existing sounds are only replaced once everything matches. Returns the installed pack.
 pub fn download_sounds(
-    terminal: &mut DefaultTerminal,
-    github_user: &str,
-    github_repo: &str,
+    reporter: &mut dyn DownloadReporter,
+    download: &DownloadConfig,
     version: &str,
-) -> Result<()> {
-    let url = format!(
-        "https://github.com/{}/{}/releases/download/v{}/sounds.tar.gz",
-        github_user, github_repo, version
-    );
-
-    run_prompt(
-        terminal,
-        "Downloading",
-        &format!("Downloading sounds from GitHub release v{}...", version),
-        PromptType::Info,
-    )?;
+    sounds_dir: &Path,
+) -> Result<PackManifest> {
+    let client = http::client(download)?;
+    let sources = download.sources(version);
+    let mut failures = Vec::new();
 
-    // Download the file
-    let response = reqwest::blocking::get(&url).context("Failed to download sounds")?;
+    for source in &sources {
+        reporter.report(
+            "Downloading",
+            &format!("Downloading sounds for v{} from {}...", version, source),
+        )?;
 
-    if !response.status().is_success() {
-        anyhow::bail!(
-            "Failed to download sounds: HTTP status {}. Make sure the release exists with sounds.tar.gz attached.",
-            response.status()
-        );
+        match download_from(reporter, &client, source, true, sounds_dir) {
+            Ok(pack) => return Ok(pack),
+            Err(error) if error.is::<DownloadCancelled>() || sources.len() == 1 => {
+                return Err(error)
+            }
+            Err(error) => failures.push((source.clone(), error)),
+        }
+    }
+
+    Err(SourcesFailed { failures }.into())
+}
+
+/// Look up the sound pack available for this player version, from the first source that has
+/// it. Each source is tried once with short timeouts, checking for updates shouldn't hold up
+/// starting.
+pub fn available_pack(
+    reporter: &mut dyn DownloadReporter,
+    download: &DownloadConfig,
+    version: &str,
+) -> Result<PackManifest> {
+    let client = http::check_client(download)?;
+    let retry = RetryPolicy {
+        attempts: 1,
+        ..RetryPolicy::default()
+    };
+    let mut last_error = None;
+
+    for source in download.sources(version) {
+        let url = format!("{}/{}", source, soundpack::MANIFEST_NAME);
+        let pack = http::fetch(&client, &url, reporter, retry).and_then(|data| {
+            let pack = PackManifest::parse(&String::from_utf8_lossy(&data))?;
+            check_kind(&pack, true)?;
+            Ok(pack)
+        });
+        match pack {
+            Ok(pack) => return Ok(pack),
+            Err(error) if error.is::<DownloadCancelled>() => return Err(error),
+            Err(error) => last_error = Some(error),
+        }
     }
 
-    let bytes = response
-        .bytes()
-        .context("Failed to read download response")?;
+    Err(last_error.unwrap_or_else(|| anyhow::anyhow!("No download source configured")))
+}
 
-    run_prompt(
-        terminal,
+/// Download and install the player's own sounds (`default`) or another pack from one source
+/// into `sounds_dir`
+fn download_from(
+    reporter: &mut dyn DownloadReporter,
+    client: &HttpClient,
+    release_url: &str,
+    default: bool,
+    sounds_dir: &Path,
+) -> Result<PackManifest> {
+    let retry = RetryPolicy::default();
+
+    let manifest = http::fetch(
+        client,
+        &format!("{}/{}", release_url, checksums::MANIFEST_NAME),
+        reporter,
+        retry,
+    )
+    .map_err(release_hint)?;
+    let checksums = Checksums::parse(&String::from_utf8_lossy(&manifest))
+        .context("Failed to read checksum manifest")?;
+
+    let pack = http::fetch(
+        client,
+        &format!("{}/{}", release_url, soundpack::MANIFEST_NAME),
+        reporter,
+        retry,
+    )
+    .map_err(release_hint)?;
+    checksums.verify_bytes(soundpack::MANIFEST_NAME, &pack)?;
+    let pack = PackManifest::parse(&String::from_utf8_lossy(&pack))?;
+    pack.check_compatible(&soundpack::player_version())?;
+    check_kind(&pack, default)?;
+
+    // Stream the archive to disk, an interrupted download continues where it stopped the next
+    // time (also after a restart)
+    let part = partial_download(sounds_dir, &pack)?;
+    http::download_resumable(
+        client,
+        &format!("{}/{}", release_url, ARCHIVE_NAME),
+        &part,
+        reporter,
+        retry,
+    )
+    .map_err(release_hint)?;
+
+    let size = fs::metadata(&part)
+        .map(|metadata| metadata.len())
+        .unwrap_or(0);
+    reporter.report(
         "Download Complete",
-        &format!("Downloaded {} KB", bytes.len() / 1024),
-        PromptType::Info,
+        &format!("Downloaded {}", format_bytes(size)),
     )?;
 
-    // Extract to data directory (archive contains sounds/ folder)
-    let proj_dirs = ProjectDirs::from("com", "relax-player", "relax-player")
-        .context("Failed to determine data directory")?;
-    let data_dir = proj_dirs.data_dir();
+    if let Err(error) = checksums.verify_file(ARCHIVE_NAME, &part) {
+        // Don't resume a corrupted download
+        let _ = fs::remove_file(&part);
+        return Err(error);
+    }
 
-    fs::create_dir_all(data_dir).context("Failed to create data directory")?;
+    reporter.report("Extracting", "Extracting sound files...")?;
 
-    let decoder = GzDecoder::new(&bytes[..]);
-    let mut archive = Archive::new(decoder);
+    with_staging_dir(sounds_dir, |staging_dir| {
+        let archive =
+            fs::File::open(&part).context(format!("Failed to open {}", part.display()))?;
+        let expected = expected_files(Some(&checksums), Some(&pack));
+        let files = extract::extract_sounds(
+            GzDecoder::new(BufReader::new(archive)),
+            staging_dir,
+            &expected,
+        )?;
+        verify_staged(&checksums, &files, staging_dir)?;
+        install_staged(&files, staging_dir, Some(&pack), sounds_dir)
+    })?;
+    let _ = fs::remove_file(&part);
 
-    run_prompt(
-        terminal,
-        "Extracting",
-        "Extracting sound files...",
-        PromptType::Info,
-    )?;
+    reporter.report("Success", "Sounds downloaded and extracted successfully!")?;
 
-    archive
-        .unpack(data_dir)
-        .context("Failed to extract sounds archive")?;
+    Ok(pack)
+}
+
+/// Sounds installed from a local archive or folder
+pub struct InstalledSounds {
+    pub files: usize,
+    /// The pack, if its `sounds.json` was next to the sounds
+    pub pack: Option<PackManifest>,
+}
 
-    // Verify all sounds were extracted
-    if !sounds_exist()? {
-        anyhow::bail!(
-            "Sound extraction completed but some files are missing. Expected: {:?}",
-            REQUIRED_SOUNDS
+/// Install sounds from a local `sounds.tar.gz` or a folder of sound files, for machines that
+/// can't reach GitHub.
+///
+/// An archive is checked against a `sounds.sha256` manifest next to it, if there is one.
+/// A `sounds.json` pack manifest next to the sounds must be compatible with this player.
+pub fn install_sounds(source: &Path, sounds_dir: &Path) -> Result<InstalledSounds> {
+    install_local(source, true, sounds_dir)
+}
+
+/// Install another sound pack, to play next to the player's own sounds. The source is either
+/// a URL serving `sounds.tar.gz`, `sounds.sha256` and `sounds.json` like a release does, or a
+/// local archive or folder with its `sounds.json` next to the sounds.
+pub fn install_pack(
+    reporter: &mut dyn DownloadReporter,
+    download: &DownloadConfig,
+    source: &str,
+    sounds_dir: &Path,
+) -> Result<PackManifest> {
+    if source.contains("://") {
+        let client = http::client(download)?;
+        reporter.report(
+            "Downloading",
+            &format!("Downloading sound pack from {}...", source),
+        )?;
+        return download_from(
+            reporter,
+            &client,
+            source.trim_end_matches('/'),
+            false,
+            sounds_dir,
         );
     }
 
-    run_prompt(
-        terminal,
-        "Success",
-        "Sounds downloaded and extracted successfully!",
-        PromptType::Info,
-    )?;
+    let installed = install_local(Path::new(source), false, sounds_dir)?;
+    installed
+        .pack
+        .context("Sound pack was installed without a manifest")
+}
+
+/// Remove an installed sound pack with all of its sounds
+pub fn remove_pack(id: &str, sounds_dir: &Path) -> Result<()> {
+    if id == soundpack::DEFAULT_PACK {
+        anyhow::bail!("The player's own sounds can't be removed");
+    }
+
+    // The id ends up in a path, so it must be a valid pack id
+    let dir = sounds_dir.join(soundpack::pack_dir(id));
+    if !soundpack::is_pack_id(id) || !dir.join(soundpack::MANIFEST_NAME).is_file() {
+        anyhow::bail!("Sound pack {} is not installed", id);
+    }
+
+    fs::remove_dir_all(&dir).context(format!("Failed to remove {}", dir.display()))
+}
+
+/// Install the player's own sounds (`default`) or another pack from a local archive or folder
+/// into `sounds_dir`
+fn install_local(source: &Path, default: bool, sounds_dir: &Path) -> Result<InstalledSounds> {
+    if !source.exists() {
+        anyhow::bail!("{} not found", source.display());
+    }
+
+    with_staging_dir(sounds_dir, |staging_dir| {
+        let (files, pack) = if source.is_dir() {
+            let pack = read_pack(&source.join(soundpack::MANIFEST_NAME), None)?;
+
+            // Either the sounds folder itself or a folder containing it
+            let sounds = source.join("sounds");
+            let dir = if sounds.is_dir() { &sounds } else { source };
+            (extract::copy_sounds(dir, staging_dir)?, pack)
+        } else {
+            let manifest = source.with_file_name(checksums::MANIFEST_NAME);
+            let checksums = if manifest.is_file() {
+                let contents = fs::read_to_string(&manifest)
+                    .context(format!("Failed to read {}", manifest.display()))?;
+                let checksums = Checksums::parse(&contents)
+                    .context(format!("Failed to read {}", manifest.display()))?;
+                checksums.verify_file(ARCHIVE_NAME, source)?;
+                Some(checksums)
+            } else {
+                None
+            };
+            let pack = read_pack(
+                &source.with_file_name(soundpack::MANIFEST_NAME),
+                checksums.as_ref(),
+            )?;
+
+            let archive =
+                fs::File::open(source).context(format!("Failed to open {}", source.display()))?;
+            let expected = expected_files(checksums.as_ref(), pack.as_ref());
+            let files = extract::extract_sounds(
+                GzDecoder::new(BufReader::new(archive)),
+                staging_dir,
+                &expected,
+            )
+            .context(format!("Failed to extract {}", source.display()))?;
+
+            if let Some(checksums) = checksums {
+                verify_staged(&checksums, &files, staging_dir)?;
+            }
+            (files, pack)
+        };
+
+        match &pack {
+            Some(pack) => check_kind(pack, default)?,
+            None if !default => anyhow::bail!(
+                "No {} found next to {}, a sound pack needs its manifest",
+                soundpack::MANIFEST_NAME,
+                source.display()
+            ),
+            None => {}
+        }
+
+        install_staged(&files, staging_dir, pack.as_ref(), sounds_dir)?;
+        Ok(InstalledSounds {
+            files: files.len(),
+            pack,
+        })
+    })
+}
+
+/// Make sure a pack is the player's own sounds (`default`) or another pack, as expected
+fn check_kind(pack: &PackManifest, default: bool) -> Result<()> {
+    match (pack.is_default(), default) {
+        (false, true) => anyhow::bail!(
+            "These are the sounds of the {} sound pack, install them with `relax-player packs install`",
+            pack.name
+        ),
+        (true, false) => anyhow::bail!(
+            "These are the player's own sounds, install them with `relax-player install-sounds`"
+        ),
+        _ => Ok(()),
+    }
+}
+
+/// Read the sound pack manifest at `path` if there is one, checked against `checksums` if
+/// given, and make sure the sounds work with this player
+fn read_pack(path: &Path, checksums: Option<&Checksums>) -> Result<Option<PackManifest>> {
+    if !path.is_file() {
+        return Ok(None);
+    }
+
+    let contents = fs::read(path).context(format!("Failed to read {}", path.display()))?;
+    if let Some(checksums) = checksums {
+        checksums.verify_bytes(soundpack::MANIFEST_NAME, &contents)?;
+    }
+    let pack = PackManifest::parse(&String::from_utf8_lossy(&contents))
+        .context(format!("Failed to read {}", path.display()))?;
+    pack.check_compatible(&soundpack::player_version())?;
+
+    Ok(Some(pack))
+}
+
+/// Run `stage` with an empty staging directory next to the sounds directory, which is removed
+/// again afterwards. Leftovers of a failed install are never used.
+fn with_staging_dir<T>(sounds_dir: &Path, stage: impl FnOnce(&Path) -> Result<T>) -> Result<T> {
+    let staging_dir = sounds_dir.with_file_name(STAGING_DIR);
+
+    if staging_dir.exists() {
+        fs::remove_dir_all(&staging_dir).context("Failed to clean up previous download")?;
+    }
+    fs::create_dir_all(&staging_dir).context("Failed to create download directory")?;
+
+    let result = stage(&staging_dir);
+    let _ = fs::remove_dir_all(&staging_dir);
+    result
+}
+
+/// Files a sounds archive may contain: the sounds in its checksum manifest, or without one the
+/// sounds of its pack
+fn expected_files(checksums: Option<&Checksums>, pack: Option<&PackManifest>) -> Vec<String> {
+    match checksums {
+        Some(checksums) => checksums
+            .files()
+            .filter(|name| name.starts_with("sounds/"))
+            .map(String::from)
+            .collect(),
+        None => pack
+            .cloned()
+            .unwrap_or_else(PackManifest::builtin)
+            .sounds
+            .iter()
+            .map(|sound| format!("sounds/{}", sound.file))
+            .collect(),
+    }
+}
+
+/// Check the staged files against the manifest: every file must be listed and match its hash,
+/// and every listed file must be there
+fn verify_staged(checksums: &Checksums, files: &[String], staging_dir: &Path) -> Result<()> {
+    for name in files {
+        checksums.verify_file(name, &staging_dir.join(name))?;
+    }
+
+    // The manifest also lists the release files next to the archive
+    for name in checksums.files().filter(|name| name.starts_with("sounds/")) {
+        if !files.iter().any(|file| file == name) {
+            anyhow::bail!("{} is missing from the sounds archive", name);
+        }
+    }
+
+    Ok(())
+}
+
+/// Move staged files (paths starting with `sounds/`) into the pack's folder in `sounds_dir`,
+/// once it's clear that all of its sounds are there. The manifest is kept with the sounds,
+/// without one they count as the sounds built into the player.
+///
+/// Other packs are swapped in as a whole folder, so an update that fails leaves the previous
+/// version in place. The default pack shares its folder with the other packs and the custom
+/// sounds, so its files are moved one by one.
+fn install_staged(
+    files: &[String],
+    staging_dir: &Path,
+    pack: Option<&PackManifest>,
+    sounds_dir: &Path,
+) -> Result<()> {
+    let target = pack.cloned().unwrap_or_else(PackManifest::builtin);
+    let missing = target.missing_files(&staging_dir.join("sounds"));
+    if !missing.is_empty() {
+        anyhow::bail!("Sound files are missing: {}", missing.join(", "));
+    }
+
+    let pack_dir = sounds_dir.join(target.dir());
+    if !target.is_default() {
+        // An update replaces the whole pack, files it no longer has don't linger
+        let staged = staging_dir.join("sounds");
+        write_manifest(&staged, pack)?;
+        return replace_dir(&staged, &pack_dir, &staging_dir.join("previous"));
+    }
+
+    for name in files {
+        let relative = name.strip_prefix("sounds/").unwrap_or(name);
+        let target = pack_dir.join(relative);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).context("Failed to create sounds directory")?;
+        }
+        fs::rename(staging_dir.join(name), &target)
+            .context(format!("Failed to install {}", target.display()))?;
+    }
+
+    write_manifest(&pack_dir, pack)
+}
+
+/// Write the pack manifest into `dir`, or remove an old one if there's no manifest
+fn write_manifest(dir: &Path, pack: Option<&PackManifest>) -> Result<()> {
+    let manifest = dir.join(soundpack::MANIFEST_NAME);
+    match pack {
+        Some(pack) => fs::write(&manifest, serde_json::to_string_pretty(pack)?)
+            .context(format!("Failed to write {}", manifest.display()))?,
+        // A manifest from earlier would describe other sounds
+        None => {
+            let _ = fs::remove_file(&manifest);
+        }
+    }
 
     Ok(())
 }
 
-/// Check if version needs update (returns true if update needed)
-pub fn needs_update(current_version: &str, stored_version: Option<&str>) -> bool {
-    match stored_version {
-        None => true, // No version stored means first install or needs update
-        Some(stored) => stored != current_version,
+/// Move the folder `new` to `target`. Whatever was at `target` is kept at `backup` until the
+/// new folder is in place, and moved back if that fails.
+fn replace_dir(new: &Path, target: &Path, backup: &Path) -> Result<()> {
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent).context("Failed to create sounds directory")?;
+    }
+
+    let replacing = target.exists();
+    if replacing {
+        fs::rename(target, backup).context(format!("Failed to move {}", target.display()))?;
+    }
+
+    if let Err(error) = fs::rename(new, target) {
+        if replacing {
+            let _ = fs::rename(backup, target);
+        }
+        return Err(error).context(format!("Failed to install {}", target.display()));
+    }
+
+    if replacing {
+        let _ = fs::remove_dir_all(backup);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use sha2::{Digest, Sha256};
+    use std::io::BufRead;
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Ignores all download status
+    struct Quiet;
+
+    impl DownloadReporter for Quiet {
+        fn report(&mut self, _title: &str, _message: &str) -> Result<()> {
+            Ok(())
+        }
+
+        fn progress(&mut self, _progress: &DownloadProgress) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    /// A local HTTP server publishing the files of `dir` as a release, returning its URL.
+    /// Files that aren't there are answered with 404.
+    fn serve_release(dir: &Path) -> String {
+        let dir = dir.to_path_buf();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/release", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut file = None;
+                for line in BufReader::new(&mut stream).lines() {
+                    let line = line.unwrap();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if let Some(path) = line.strip_prefix("GET /release/") {
+                        let name = path.split(' ').next().unwrap_or_default();
+                        file = fs::read(dir.join(name)).ok();
+                    }
+                }
+
+                let response = match file {
+                    Some(data) => [
+                        format!(
+                            "HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: {}\r\n\r\n",
+                            data.len()
+                        )
+                        .into_bytes(),
+                        data,
+                    ]
+                    .concat(),
+                    None => {
+                        b"HTTP/1.1 404 Not Found\r\nConnection: close\r\nContent-Length: 0\r\n\r\n"
+                            .to_vec()
+                    }
+                };
+                let _ = stream.write_all(&response);
+            }
+        });
+        url
+    }
+
+    /// A local HTTP server answering every request with 404, returning its URL
+    fn missing_release() -> String {
+        serve_release(Path::new("/nonexistent"))
+    }
+
+    #[test]
+    fn explains_failures_of_all_sources() {
+        let download = DownloadConfig {
+            base_url: Some(missing_release()),
+            mirrors: vec![missing_release()],
+            ..DownloadConfig::default()
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let error = download_sounds(&mut Quiet, &download, "1.0.0", &dir.path().join("sounds"))
+            .unwrap_err();
+
+        assert!(error.is::<SourcesFailed>());
+        assert_eq!(error.to_string().matches("404").count(), 2);
+        assert_eq!(failure_help(&error).0, "HTTP Error");
+    }
+
+    /// Write an ocean pack archive with the given sound files into `dir`, with its manifest
+    /// next to it, returning the archive's path
+    fn write_pack(dir: &Path, version: &str, files: &[&str]) -> String {
+        fs::create_dir_all(dir).unwrap();
+
+        let sounds: Vec<_> = files
+            .iter()
+            .map(|file| {
+                let id = file.trim_end_matches(".mp3");
+                format!(
+                    r#"{{"id": "{}", "name": "{}", "file": "{}", "license": "CC0"}}"#,
+                    id, id, file
+                )
+            })
+            .collect();
+        let manifest = format!(
+            r#"{{"id": "ocean", "name": "Ocean", "version": "{}", "player": "*", "sounds": [{}]}}"#,
+            version,
+            sounds.join(",")
+        );
+        fs::write(dir.join(soundpack::MANIFEST_NAME), manifest).unwrap();
+
+        let archive = dir.join(ARCHIVE_NAME);
+        let encoder = GzEncoder::new(fs::File::create(&archive).unwrap(), Compression::fast());
+        let mut builder = tar::Builder::new(encoder);
+        for file in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(4);
+            header.set_mode(0o644);
+            builder
+                .append_data(&mut header, format!("sounds/{}", file), &b"wave"[..])
+                .unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+
+        archive.to_string_lossy().into_owned()
+    }
+
+    fn install(source: &str, sounds_dir: &Path) -> Result<PackManifest> {
+        install_pack(&mut Quiet, &DownloadConfig::default(), source, sounds_dir)
+    }
+
+    #[test]
+    fn installs_pack_into_sounds_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let sounds_dir = dir.path().join("sounds");
+        let archive = write_pack(&dir.path().join("v1"), "1.0.0", &["waves.mp3"]);
+
+        let pack = install(&archive, &sounds_dir).unwrap();
+
+        assert_eq!(pack.id, "ocean");
+        let pack_dir = sounds_dir.join("packs/ocean");
+        assert_eq!(fs::read(pack_dir.join("waves.mp3")).unwrap(), b"wave");
+        assert_eq!(
+            soundpack::read_manifest(&pack_dir.join("sounds.json")).unwrap(),
+            pack
+        );
+        // The staging directory is cleaned up again
+        assert!(!dir.path().join(STAGING_DIR).exists());
+    }
+
+    #[test]
+    fn downloads_pack_next_to_sounds_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let release = dir.path().join("release");
+        write_pack(&release, "1.0.0", &["waves.mp3"]);
+        let checksums: String = [ARCHIVE_NAME, soundpack::MANIFEST_NAME]
+            .iter()
+            .map(|name| (name.to_string(), fs::read(release.join(name)).unwrap()))
+            .chain([("sounds/waves.mp3".to_string(), b"wave".to_vec())])
+            .map(|(name, data)| format!("{}  {}\n", hex::encode(Sha256::digest(data)), name))
+            .collect();
+        fs::write(release.join(checksums::MANIFEST_NAME), checksums).unwrap();
+
+        let sounds_dir = dir.path().join("data/sounds");
+        install(&serve_release(&release), &sounds_dir).unwrap();
+
+        assert!(sounds_dir.join("packs/ocean/waves.mp3").exists());
+        // The finished download and the staging directory are gone again
+        let mut left: Vec<_> = fs::read_dir(dir.path().join("data"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        left.sort();
+        assert_eq!(left, ["sounds"]);
+    }
+
+    #[test]
+    fn update_replaces_pack() {
+        let dir = tempfile::tempdir().unwrap();
+        let sounds_dir = dir.path().join("sounds");
+        let old = write_pack(&dir.path().join("v1"), "1.0.0", &["waves.mp3", "gulls.mp3"]);
+        let new = write_pack(&dir.path().join("v2"), "2.0.0", &["waves.mp3"]);
+
+        install(&old, &sounds_dir).unwrap();
+        install(&new, &sounds_dir).unwrap();
+
+        let pack_dir = sounds_dir.join("packs/ocean");
+        assert!(pack_dir.join("waves.mp3").exists());
+        assert!(!pack_dir.join("gulls.mp3").exists());
+        let installed = soundpack::installed_packs(&sounds_dir).unwrap();
+        assert_eq!(installed.len(), 2);
+        assert_eq!(installed[1].version.to_string(), "2.0.0");
+    }
+
+    #[test]
+    fn failed_update_keeps_previous_pack() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("packs/ocean");
+        fs::create_dir_all(&target).unwrap();
+        fs::write(target.join("waves.mp3"), b"old").unwrap();
+
+        // The new folder is gone, so it can't be moved into place
+        let result = replace_dir(
+            &dir.path().join("missing"),
+            &target,
+            &dir.path().join("previous"),
+        );
+
+        assert!(result.is_err());
+        assert_eq!(fs::read(target.join("waves.mp3")).unwrap(), b"old");
+        assert!(!dir.path().join("previous").exists());
+    }
+
+    #[test]
+    fn removes_only_installed_packs() {
+        let dir = tempfile::tempdir().unwrap();
+        let sounds_dir = dir.path().join("sounds");
+        install(
+            &write_pack(&dir.path().join("v1"), "1.0.0", &["waves.mp3"]),
+            &sounds_dir,
+        )
+        .unwrap();
+
+        // A folder with a manifest outside the packs folder
+        let outside = dir.path().join("outside");
+        write_pack(&outside, "1.0.0", &["waves.mp3"]);
+
+        assert!(remove_pack(soundpack::DEFAULT_PACK, &sounds_dir).is_err());
+        assert!(remove_pack("../../outside", &sounds_dir).is_err());
+        assert!(remove_pack("forest", &sounds_dir).is_err());
+        assert!(outside.join(soundpack::MANIFEST_NAME).exists());
+
+        remove_pack("ocean", &sounds_dir).unwrap();
+        assert!(!sounds_dir.join("packs/ocean").exists());
     }
 }
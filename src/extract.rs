@@ -0,0 +1,356 @@
+use anyhow::{Context, Result};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+use tar::{Archive, EntryType};
+
+use crate::channels;
+
+/// Top level directory every file in a sounds archive must be in
+const SOUNDS_DIR: &str = "sounds";
+
+/// How deep files may be below the sounds directory, e.g. sounds/thunder/strike1.mp3
+const MAX_DEPTH: usize = 2;
+
+/// Largest single file accepted from a sounds archive
+const MAX_ENTRY_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Largest total size of all files in a sounds archive
+const MAX_TOTAL_BYTES: u64 = 1024 * 1024 * 1024;
+
+/// Size limits for extraction
+#[derive(Debug, Clone, Copy)]
+struct Limits {
+    entry_bytes: u64,
+    total_bytes: u64,
+}
+
+/// Extract a sounds archive into `dest`, returning the extracted files as '/' separated paths
+/// relative to `dest`.
+///
+/// Only the `expected` sound files below `sounds/` are accepted, given like the returned paths.
+/// The whole archive is rejected if it contains anything else, such as absolute paths, `..`
+/// components, links, unlisted or oversized files, so a malicious archive can't write outside
+/// `dest` or fill up the disk.
+pub fn extract_sounds(archive: impl Read, dest: &Path, expected: &[String]) -> Result<Vec<String>> {
+    extract_with_limits(
+        archive,
+        dest,
+        expected,
+        Limits {
+            entry_bytes: MAX_ENTRY_BYTES,
+            total_bytes: MAX_TOTAL_BYTES,
+        },
+    )
+}
+
+fn extract_with_limits(
+    archive: impl Read,
+    dest: &Path,
+    expected: &[String],
+    limits: Limits,
+) -> Result<Vec<String>> {
+    let mut archive = Archive::new(archive);
+    let mut files = Vec::new();
+    let mut total_bytes = 0;
+
+    for entry in archive.entries().context("Failed to read sounds archive")? {
+        let mut entry = entry.context("Failed to read sounds archive")?;
+        let raw_path = entry.path_bytes().into_owned();
+        let name = String::from_utf8_lossy(&raw_path).into_owned();
+        let entry_type = entry.header().entry_type();
+
+        // Archive wide metadata, nothing to extract
+        if entry_type == EntryType::XGlobalHeader {
+            continue;
+        }
+
+        let parts = checked_parts(&name)?;
+        let relative: PathBuf = parts.iter().collect();
+
+        match entry_type {
+            EntryType::Directory => {
+                fs::create_dir_all(dest.join(&relative))
+                    .context(format!("Failed to create directory for {}", name))?;
+            }
+            EntryType::Regular | EntryType::Continuous => {
+                let file_name = parts.join("/");
+                if parts.len() < 2
+                    || parts.len() > MAX_DEPTH + 1
+                    || !channels::is_sound_file(&relative)
+                    || !expected.contains(&file_name)
+                {
+                    anyhow::bail!("Unexpected file in sounds archive: {}", name);
+                }
+
+                let size = entry.size();
+                total_bytes += size;
+                if size > limits.entry_bytes || total_bytes > limits.total_bytes {
+                    anyhow::bail!("File in sounds archive is too large: {}", name);
+                }
+
+                let target = dest.join(&relative);
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent)
+                        .context(format!("Failed to create directory for {}", name))?;
+                }
+
+                // A new file never follows a link that's already there
+                let mut file = OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .open(&target)
+                    .context(format!("Failed to extract {}", name))?;
+                io::copy(&mut (&mut entry).take(size), &mut file)
+                    .context(format!("Failed to extract {}", name))?;
+
+                files.push(file_name);
+            }
+            _ => anyhow::bail!("Unsupported entry in sounds archive: {}", name),
+        }
+    }
+
+    Ok(files)
+}
+
+/// Copy the sound files of a folder into `dest/sounds/`, returning them like `extract_sounds`.
+/// Other files are skipped, so a folder with e.g. a readme next to the sounds can be used.
+pub fn copy_sounds(dir: &Path, dest: &Path) -> Result<Vec<String>> {
+    let mut files = Vec::new();
+    copy_dir(
+        dir,
+        &dest.join(SOUNDS_DIR),
+        &[SOUNDS_DIR.to_string()],
+        &mut files,
+    )?;
+    Ok(files)
+}
+
+fn copy_dir(dir: &Path, target: &Path, parts: &[String], files: &mut Vec<String>) -> Result<()> {
+    let entries =
+        fs::read_dir(dir).context(format!("Failed to read directory: {}", dir.display()))?;
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        let Some(name) = path.file_name() else {
+            continue;
+        };
+        let mut entry_parts = parts.to_vec();
+        entry_parts.push(name.to_string_lossy().into_owned());
+
+        if path.is_dir() {
+            if entry_parts.len() <= MAX_DEPTH {
+                copy_dir(&path, &target.join(name), &entry_parts, files)?;
+            }
+        } else if path.is_file() && channels::is_sound_file(&path) {
+            fs::create_dir_all(target)
+                .context(format!("Failed to create directory: {}", target.display()))?;
+            fs::copy(&path, target.join(name))
+                .context(format!("Failed to copy {}", path.display()))?;
+            files.push(entry_parts.join("/"));
+        }
+    }
+
+    Ok(())
+}
+
+/// Split an archive path into its parts, rejecting paths that could end up outside the
+/// extraction directory or outside `sounds/`
+fn checked_parts(name: &str) -> Result<Vec<String>> {
+    let mut parts = Vec::new();
+
+    for component in Path::new(name).components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_string_lossy().into_owned()),
+            Component::CurDir => {}
+            Component::RootDir | Component::Prefix(_) => {
+                anyhow::bail!("Absolute path in sounds archive: {}", name)
+            }
+            Component::ParentDir => anyhow::bail!("Path with '..' in sounds archive: {}", name),
+        }
+    }
+
+    if parts.first().map(String::as_str) != Some(SOUNDS_DIR) {
+        anyhow::bail!("Unexpected file in sounds archive: {}", name);
+    }
+
+    Ok(parts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tar::{Builder, Header};
+
+    const LIMITS: Limits = Limits {
+        entry_bytes: 1024,
+        total_bytes: 2048,
+    };
+
+    /// Header for an entry, with the name written as-is so paths the tar crate would refuse to
+    /// create can be tested
+    fn header(name: &str, entry_type: EntryType, size: u64) -> Header {
+        let mut header = Header::new_gnu();
+        header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+        header.set_entry_type(entry_type);
+        header.set_size(size);
+        header.set_mode(0o644);
+        header.set_cksum();
+        header
+    }
+
+    /// Build an archive of regular files
+    fn archive(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut builder = Builder::new(Vec::new());
+        for (name, data) in files {
+            let header = header(name, EntryType::Regular, data.len() as u64);
+            builder.append(&header, *data).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    fn extract(archive: &[u8]) -> (tempfile::TempDir, Result<Vec<String>>) {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("staging");
+        fs::create_dir(&dest).unwrap();
+        let expected = [
+            "sounds/rain.mp3",
+            "sounds/thunder/strike1.ogg",
+            "sounds/big.mp3",
+            "sounds/1.mp3",
+            "sounds/2.mp3",
+            "sounds/3.mp3",
+            "sounds/evil.sh",
+            "sounds/a/b/c.mp3",
+        ]
+        .map(String::from);
+        let result = extract_with_limits(archive, &dest, &expected, LIMITS);
+        (dir, result)
+    }
+
+    fn assert_rejected(archive: &[u8], message: &str) {
+        let (dir, result) = extract(archive);
+        let error = result.unwrap_err().to_string();
+        assert!(error.contains(message), "unexpected error: {}", error);
+
+        // Nothing was written next to the extraction directory
+        let entries: Vec<_> = fs::read_dir(dir.path()).unwrap().collect();
+        assert_eq!(entries.len(), 1);
+    }
+
+    #[test]
+    fn extracts_sound_files() {
+        let mut builder = Builder::new(Vec::new());
+        builder
+            .append(&header("sounds/", EntryType::Directory, 0), io::empty())
+            .unwrap();
+        builder
+            .append(
+                &header("sounds/rain.mp3", EntryType::Regular, 4),
+                &b"rain"[..],
+            )
+            .unwrap();
+        builder
+            .append(
+                &header("./sounds/thunder/strike1.ogg", EntryType::Regular, 6),
+                &b"strike"[..],
+            )
+            .unwrap();
+        let archive = builder.into_inner().unwrap();
+
+        let (dir, result) = extract(&archive);
+        assert_eq!(
+            result.unwrap(),
+            ["sounds/rain.mp3", "sounds/thunder/strike1.ogg"]
+        );
+
+        let staging = dir.path().join("staging");
+        assert_eq!(fs::read(staging.join("sounds/rain.mp3")).unwrap(), b"rain");
+        assert_eq!(
+            fs::read(staging.join("sounds/thunder/strike1.ogg")).unwrap(),
+            b"strike"
+        );
+    }
+
+    #[test]
+    fn copies_sound_files_from_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("source");
+        fs::create_dir_all(source.join("thunder/a/b")).unwrap();
+        fs::write(source.join("rain.mp3"), b"rain").unwrap();
+        fs::write(source.join("README.md"), b"readme").unwrap();
+        fs::write(source.join("thunder/strike1.ogg"), b"strike").unwrap();
+        fs::write(source.join("thunder/a/too-deep.mp3"), b"deep").unwrap();
+
+        let dest = dir.path().join("staging");
+        let files = copy_sounds(&source, &dest).unwrap();
+
+        assert_eq!(files, ["sounds/rain.mp3", "sounds/thunder/strike1.ogg"]);
+        assert_eq!(fs::read(dest.join("sounds/rain.mp3")).unwrap(), b"rain");
+        assert!(!dest.join("sounds/README.md").exists());
+    }
+
+    #[test]
+    fn rejects_path_traversal() {
+        assert_rejected(&archive(&[("/tmp/evil.mp3", b"evil")]), "Absolute path");
+        assert_rejected(&archive(&[("../evil.mp3", b"evil")]), "'..'");
+        assert_rejected(&archive(&[("sounds/../../evil.mp3", b"evil")]), "'..'");
+    }
+
+    #[test]
+    fn rejects_files_not_on_allow_list() {
+        assert_rejected(&archive(&[("evil.mp3", b"evil")]), "Unexpected file");
+        assert_rejected(&archive(&[("other/rain.mp3", b"evil")]), "Unexpected file");
+        assert_rejected(&archive(&[("sounds/evil.sh", b"evil")]), "Unexpected file");
+        assert_rejected(
+            &archive(&[("sounds/a/b/c.mp3", b"evil")]),
+            "Unexpected file",
+        );
+        // A sound file, but not one the pack has
+        assert_rejected(
+            &archive(&[("sounds/rain.mp3", b"rain"), ("sounds/extra.mp3", b"evil")]),
+            "Unexpected file",
+        );
+    }
+
+    #[test]
+    fn rejects_links() {
+        for entry_type in [EntryType::Symlink, EntryType::Link] {
+            let mut link = header("sounds/rain.mp3", entry_type, 0);
+            link.set_link_name("/etc/passwd").unwrap();
+            link.set_cksum();
+
+            let mut builder = Builder::new(Vec::new());
+            builder.append(&link, io::empty()).unwrap();
+            // A file written through the link would end up outside the extraction directory
+            builder
+                .append(
+                    &header("sounds/rain.mp3", EntryType::Regular, 4),
+                    &b"evil"[..],
+                )
+                .unwrap();
+
+            assert_rejected(&builder.into_inner().unwrap(), "Unsupported entry");
+        }
+    }
+
+    #[test]
+    fn rejects_oversized_files() {
+        let big = vec![0; 1025];
+        assert_rejected(&archive(&[("sounds/big.mp3", &big)]), "too large");
+
+        // Each file is small enough, but not all of them together
+        let file = vec![0; 1000];
+        assert_rejected(
+            &archive(&[
+                ("sounds/1.mp3", &file),
+                ("sounds/2.mp3", &file),
+                ("sounds/3.mp3", &file),
+            ]),
+            "too large",
+        );
+    }
+}
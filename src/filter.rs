@@ -0,0 +1,364 @@
+use rodio::Source;
+use std::f64::consts::PI;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Lowest cutoff frequency a filter can be set to
+pub const MIN_CUTOFF_HZ: u32 = 20;
+
+/// Highest cutoff frequency a filter can be set to
+pub const MAX_CUTOFF_HZ: u32 = 20000;
+
+/// Low-pass cutoffs the TUI steps through, from most to least muffled
+const LOW_PASS_STEPS_HZ: [u32; 6] = [250, 500, 1000, 2000, 4000, 8000];
+
+/// High-pass cutoffs the TUI steps through, from fullest to thinnest sound
+const HIGH_PASS_STEPS_HZ: [u32; 6] = [50, 100, 200, 400, 800, 1600];
+
+/// Next low-pass cutoff when raising or lowering it by one step.
+/// Raising past the highest step turns the filter off, lowering stops at the lowest step.
+pub fn step_low_pass(current: Option<u32>, raise: bool) -> Option<u32> {
+    // Off lets everything through, like a cutoff above all steps
+    let Some(current) = current else {
+        return (!raise).then(|| LOW_PASS_STEPS_HZ[LOW_PASS_STEPS_HZ.len() - 1]);
+    };
+
+    if raise {
+        LOW_PASS_STEPS_HZ.into_iter().find(|step| *step > current)
+    } else {
+        LOW_PASS_STEPS_HZ
+            .into_iter()
+            .rev()
+            .find(|step| *step < current)
+            .or(Some(current))
+    }
+}
+
+/// Next high-pass cutoff when raising or lowering it by one step.
+/// Lowering past the lowest step turns the filter off, raising stops at the highest step.
+pub fn step_high_pass(current: Option<u32>, raise: bool) -> Option<u32> {
+    // Off lets everything through, like a cutoff below all steps
+    let Some(current) = current else {
+        return raise.then_some(HIGH_PASS_STEPS_HZ[0]);
+    };
+
+    if raise {
+        HIGH_PASS_STEPS_HZ
+            .into_iter()
+            .find(|step| *step > current)
+            .or(Some(current))
+    } else {
+        HIGH_PASS_STEPS_HZ
+            .into_iter()
+            .rev()
+            .find(|step| *step < current)
+    }
+}
+
+/// Describe a cutoff frequency for display, e.g. "250" or "2k"
+// `is_multiple_of` would need Rust 1.87
+#[allow(clippy::manual_is_multiple_of)]
+pub fn format_cutoff(hz: u32) -> String {
+    if hz >= 1000 && hz % 1000 == 0 {
+        format!("{}k", hz / 1000)
+    } else {
+        hz.to_string()
+    }
+}
+
+/// Cutoff frequencies of a channel's tone filter, shared between the player and the
+/// channel's audio source
+#[derive(Debug, Clone, Default)]
+pub struct FilterControl(Arc<Cutoffs>);
+
+/// Cutoffs in Hz, 0 when the filter is off
+#[derive(Debug, Default)]
+struct Cutoffs {
+    low_pass: AtomicU32,
+    high_pass: AtomicU32,
+}
+
+impl FilterControl {
+    /// Set the cutoff frequencies, `None` turns a filter off
+    pub fn set(&self, low_pass_hz: Option<u32>, high_pass_hz: Option<u32>) {
+        self.0
+            .low_pass
+            .store(low_pass_hz.unwrap_or(0), Ordering::Relaxed);
+        self.0
+            .high_pass
+            .store(high_pass_hz.unwrap_or(0), Ordering::Relaxed);
+    }
+
+    fn get(&self) -> (u32, u32) {
+        (
+            self.0.low_pass.load(Ordering::Relaxed),
+            self.0.high_pass.load(Ordering::Relaxed),
+        )
+    }
+}
+
+/// Normalized coefficients of a second order (biquad) filter
+#[derive(Debug, Clone, Copy)]
+struct Coefficients {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+}
+
+impl Coefficients {
+    /// Butterworth low-pass or high-pass, from the Audio EQ Cookbook
+    fn new(cutoff_hz: u32, sample_rate: u32, high_pass: bool) -> Self {
+        // Keep the cutoff below the Nyquist frequency
+        let cutoff = (cutoff_hz as f64).min(sample_rate as f64 * 0.45);
+        let w0 = 2.0 * PI * cutoff / sample_rate as f64;
+        let cos = w0.cos();
+        let alpha = w0.sin() / 2.0_f64.sqrt();
+
+        let (b0, b1) = if high_pass {
+            ((1.0 + cos) / 2.0, -(1.0 + cos))
+        } else {
+            ((1.0 - cos) / 2.0, 1.0 - cos)
+        };
+        let a0 = 1.0 + alpha;
+
+        Self {
+            b0: (b0 / a0) as f32,
+            b1: (b1 / a0) as f32,
+            b2: (b0 / a0) as f32,
+            a1: (-2.0 * cos / a0) as f32,
+            a2: ((1.0 - alpha) / a0) as f32,
+        }
+    }
+}
+
+/// Delay line of one biquad for one audio channel (transposed direct form II)
+#[derive(Debug, Clone, Copy, Default)]
+struct BiquadState {
+    z1: f32,
+    z2: f32,
+}
+
+impl BiquadState {
+    fn process(&mut self, c: &Coefficients, input: f32) -> f32 {
+        let output = c.b0 * input + self.z1;
+        self.z1 = c.b1 * input - c.a1 * output + self.z2;
+        self.z2 = c.b2 * input - c.a2 * output;
+        output
+    }
+}
+
+/// A single filter stage: off, or a biquad with its state for each audio channel
+#[derive(Debug, Default)]
+struct Stage {
+    cutoff_hz: u32,
+    coefficients: Option<Coefficients>,
+    states: Vec<BiquadState>,
+}
+
+impl Stage {
+    /// Switch to a new cutoff (0 for off), keeping the filter state if it was already on
+    fn configure(&mut self, cutoff_hz: u32, sample_rate: u32, channels: u16, high_pass: bool) {
+        self.cutoff_hz = cutoff_hz;
+        self.coefficients =
+            (cutoff_hz > 0).then(|| Coefficients::new(cutoff_hz, sample_rate.max(1), high_pass));
+
+        if self.coefficients.is_none() {
+            self.states.clear();
+        } else {
+            self.states
+                .resize(channels as usize, BiquadState::default());
+        }
+    }
+
+    fn process(&mut self, channel: usize, sample: f32) -> f32 {
+        match (&self.coefficients, self.states.get_mut(channel)) {
+            (Some(coefficients), Some(state)) => state.process(coefficients, sample),
+            _ => sample,
+        }
+    }
+}
+
+/// Source that applies a channel's low-pass and high-pass filters, e.g. to make rain sound
+/// like it's heard through a window. Cutoff changes are picked up between frames.
+pub struct Filtered<S> {
+    source: S,
+    control: FilterControl,
+    low_pass: Stage,
+    high_pass: Stage,
+    /// Format the filters were configured for
+    sample_rate: u32,
+    channels: u16,
+    /// Channel of the next sample within its frame
+    channel: u16,
+}
+
+impl<S> Filtered<S>
+where
+    S: Source<Item = f32>,
+{
+    pub fn new(source: S, control: FilterControl) -> Self {
+        Self {
+            source,
+            control,
+            low_pass: Stage::default(),
+            high_pass: Stage::default(),
+            sample_rate: 0,
+            channels: 0,
+            channel: 0,
+        }
+    }
+
+    /// Reconfigure the filters if the cutoffs or the source format changed
+    fn update_filters(&mut self) {
+        let (low_pass, high_pass) = self.control.get();
+        let sample_rate = self.source.sample_rate();
+        let channels = self.source.channels().max(1);
+
+        let format_changed = sample_rate != self.sample_rate || channels != self.channels;
+        self.sample_rate = sample_rate;
+        self.channels = channels;
+
+        if format_changed || low_pass != self.low_pass.cutoff_hz {
+            self.low_pass
+                .configure(low_pass, sample_rate, channels, false);
+        }
+        if format_changed || high_pass != self.high_pass.cutoff_hz {
+            self.high_pass
+                .configure(high_pass, sample_rate, channels, true);
+        }
+    }
+}
+
+impl<S> Iterator for Filtered<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.channel == 0 {
+            self.update_filters();
+        }
+
+        let sample = self.source.next()?;
+        let channel = self.channel as usize;
+        self.channel = (self.channel + 1) % self.channels;
+
+        let sample = self.high_pass.process(channel, sample);
+        Some(self.low_pass.process(channel, sample))
+    }
+}
+
+impl<S> Source for Filtered<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+    use rodio::source::SineWave;
+
+    /// Root mean square of the second half of the samples, after the filter settled
+    fn settled_rms(samples: &[f32]) -> f32 {
+        let settled = &samples[samples.len() / 2..];
+        (settled.iter().map(|s| s * s).sum::<f32>() / settled.len() as f32).sqrt()
+    }
+
+    fn filtered(
+        source: impl Source<Item = f32>,
+        low_pass: Option<u32>,
+        high_pass: Option<u32>,
+    ) -> Vec<f32> {
+        let control = FilterControl::default();
+        control.set(low_pass, high_pass);
+        Filtered::new(source, control).take(48_000).collect()
+    }
+
+    #[test]
+    fn low_pass_cuts_high_tones_and_keeps_dc() {
+        let tone = filtered(SineWave::new(8000.0), Some(500), None);
+        assert!(settled_rms(&tone) < 0.01, "{}", settled_rms(&tone));
+
+        let low = filtered(SineWave::new(100.0), Some(2000), None);
+        assert!((settled_rms(&low) - 0.5f32.sqrt()).abs() < 0.02);
+
+        let dc = filtered(
+            SamplesBuffer::new(1, 48_000, vec![0.5; 48_000]),
+            Some(500),
+            None,
+        );
+        assert!((dc[dc.len() - 1] - 0.5).abs() < 1e-3);
+    }
+
+    #[test]
+    fn high_pass_cuts_dc_and_low_tones() {
+        let dc = filtered(
+            SamplesBuffer::new(1, 48_000, vec![0.5; 48_000]),
+            None,
+            Some(200),
+        );
+        assert!(dc[dc.len() - 1].abs() < 1e-3);
+
+        let tone = filtered(SineWave::new(30.0), None, Some(1600));
+        assert!(settled_rms(&tone) < 0.01, "{}", settled_rms(&tone));
+    }
+
+    #[test]
+    fn filters_off_pass_samples_unchanged() {
+        let input: Vec<f32> = SineWave::new(440.0).take(1000).collect();
+        let output = filtered(SamplesBuffer::new(1, 48_000, input.clone()), None, None);
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn steps_low_pass_and_switches_it_off_past_the_top() {
+        assert_eq!(step_low_pass(None, false), Some(8000));
+        assert_eq!(step_low_pass(None, true), None);
+        assert_eq!(step_low_pass(Some(8000), true), None);
+        assert_eq!(step_low_pass(Some(1000), true), Some(2000));
+        assert_eq!(step_low_pass(Some(1000), false), Some(500));
+        // Stays at the lowest step, and gets back on the steps from a custom cutoff
+        assert_eq!(step_low_pass(Some(250), false), Some(250));
+        assert_eq!(step_low_pass(Some(300), true), Some(500));
+    }
+
+    #[test]
+    fn steps_high_pass_and_switches_it_off_past_the_bottom() {
+        assert_eq!(step_high_pass(None, true), Some(50));
+        assert_eq!(step_high_pass(None, false), None);
+        assert_eq!(step_high_pass(Some(50), false), None);
+        assert_eq!(step_high_pass(Some(200), true), Some(400));
+        assert_eq!(step_high_pass(Some(200), false), Some(100));
+        assert_eq!(step_high_pass(Some(1600), true), Some(1600));
+        assert_eq!(step_high_pass(Some(300), false), Some(200));
+    }
+
+    #[test]
+    fn formats_cutoffs() {
+        assert_eq!(format_cutoff(250), "250");
+        assert_eq!(format_cutoff(1500), "1500");
+        assert_eq!(format_cutoff(2000), "2k");
+        assert_eq!(format_cutoff(20000), "20k");
+    }
+}
@@ -0,0 +1,417 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Samples per channel in each frame (except the last one, which may be shorter)
+const BLOCK_SIZE: usize = 4096;
+
+/// Bits per sample written
+const BITS_PER_SAMPLE: u32 = 16;
+
+/// Highest order of the fixed predictors
+const MAX_FIXED_ORDER: usize = 4;
+
+/// Highest Rice parameter, 15 is the escape code
+const MAX_RICE_PARAMETER: u32 = 14;
+
+/// Offset of the STREAMINFO fields that are only known at the end: sample rate, channels,
+/// bits per sample and total samples. Follows "fLaC", the metadata block header and the
+/// block and frame sizes.
+const STREAMINFO_TOTALS_OFFSET: u64 = 4 + 4 + 10;
+
+/// Writes 16-bit samples to a FLAC file, like `hound::WavWriter` does for WAV.
+///
+/// Each block is stored with the fixed predictor that suits it best and Rice coded
+/// residuals, silence as a single constant. That's the simple end of what FLAC can do, but
+/// still leaves the file a good deal smaller than a WAV file.
+pub struct FlacWriter<W: Write + Seek> {
+    writer: W,
+    channels: usize,
+    sample_rate: u32,
+    /// Samples of the block being collected, one buffer per channel
+    block: Vec<Vec<i32>>,
+    /// Channel the next interleaved sample belongs to
+    next_channel: usize,
+    frame_number: u64,
+    /// Samples per channel written so far
+    total_samples: u64,
+}
+
+impl FlacWriter<BufWriter<File>> {
+    pub fn create(path: &Path, channels: u16, sample_rate: u32) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?), channels, sample_rate)
+    }
+}
+
+impl<W: Write + Seek> FlacWriter<W> {
+    pub fn new(mut writer: W, channels: u16, sample_rate: u32) -> io::Result<Self> {
+        if !(1..=8).contains(&channels) || sample_rate == 0 || sample_rate >= 1 << 20 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "FLAC supports 1-8 channels and sample rates below 1 MHz",
+            ));
+        }
+
+        let mut header = BitWriter::default();
+        header.write(u32::from_be_bytes(*b"fLaC") as u64, 32);
+        // The only metadata block: STREAMINFO (type 0), 34 bytes
+        header.write(1, 1);
+        header.write(0, 7);
+        header.write(34, 24);
+        header.write(BLOCK_SIZE as u64, 16);
+        header.write(BLOCK_SIZE as u64, 16);
+        // Frame sizes and the MD5 signature are optional, zero means unknown
+        header.write(0, 24);
+        header.write(0, 24);
+        header
+            .bytes
+            .extend(stream_totals(channels as usize, sample_rate, 0));
+        header.bytes.extend([0; 16]);
+        writer.write_all(&header.bytes)?;
+
+        Ok(Self {
+            writer,
+            channels: channels as usize,
+            sample_rate,
+            block: vec![Vec::with_capacity(BLOCK_SIZE); channels as usize],
+            next_channel: 0,
+            frame_number: 0,
+            total_samples: 0,
+        })
+    }
+
+    /// Add the next sample, interleaved like in a WAV file
+    pub fn write_sample(&mut self, sample: i16) -> io::Result<()> {
+        self.block[self.next_channel].push(sample as i32);
+        self.next_channel = (self.next_channel + 1) % self.channels;
+
+        if self.next_channel == 0 && self.block[0].len() == BLOCK_SIZE {
+            self.write_frame()?;
+        }
+        Ok(())
+    }
+
+    /// Write the last block and the stream length, which a player needs to know the duration
+    pub fn finalize(mut self) -> io::Result<()> {
+        // A sample that's missing from the last interleaved frame is written as silence
+        while self.next_channel != 0 {
+            self.write_sample(0)?;
+        }
+        if !self.block[0].is_empty() {
+            self.write_frame()?;
+        }
+
+        self.writer
+            .seek(SeekFrom::Start(STREAMINFO_TOTALS_OFFSET))?;
+        self.writer.write_all(&stream_totals(
+            self.channels,
+            self.sample_rate,
+            self.total_samples,
+        ))?;
+        self.writer.flush()
+    }
+
+    fn write_frame(&mut self) -> io::Result<()> {
+        let samples = self.block[0].len();
+        let mut frame = BitWriter::default();
+
+        // Sync code and fixed block size strategy
+        frame.write(0xfff8, 16);
+        let block_size_code = if samples == BLOCK_SIZE {
+            0b1100
+        } else {
+            0b0111
+        };
+        frame.write(block_size_code, 4);
+        // Sample rate from STREAMINFO
+        frame.write(0, 4);
+        // Channels stored independently
+        frame.write(self.channels as u64 - 1, 4);
+        frame.write(0b100, 3);
+        frame.write(0, 1);
+        write_coded_number(&mut frame, self.frame_number);
+        if block_size_code == 0b0111 {
+            frame.write(samples as u64 - 1, 16);
+        }
+        let crc = crc8(&frame.bytes);
+        frame.write(crc as u64, 8);
+
+        for channel in &self.block {
+            write_subframe(&mut frame, channel);
+        }
+        frame.align();
+        let crc = crc16(&frame.bytes);
+        frame.write(crc as u64, 16);
+
+        self.writer.write_all(&frame.bytes)?;
+        self.frame_number += 1;
+        self.total_samples += samples as u64;
+        for channel in &mut self.block {
+            channel.clear();
+        }
+        Ok(())
+    }
+}
+
+/// Sample rate, channels, bits per sample and total samples, as stored in STREAMINFO
+fn stream_totals(channels: usize, sample_rate: u32, total_samples: u64) -> [u8; 8] {
+    let mut totals = BitWriter::default();
+    totals.write(sample_rate as u64, 20);
+    totals.write(channels as u64 - 1, 3);
+    totals.write(BITS_PER_SAMPLE as u64 - 1, 5);
+    totals.write(total_samples, 36);
+    totals
+        .bytes
+        .try_into()
+        .expect("STREAMINFO totals are 8 bytes")
+}
+
+/// Encode one channel of a block with whatever takes the fewest bits
+fn write_subframe(out: &mut BitWriter, samples: &[i32]) {
+    // Header: padding bit, type and no wasted bits
+    if samples.iter().all(|sample| *sample == samples[0]) {
+        out.write(0, 8);
+        write_signed(out, samples[0]);
+        return;
+    }
+
+    let (order, residuals) = best_fixed_prediction(samples);
+    let (parameter, bits) = best_rice_parameter(&residuals);
+    let verbatim_bits = samples.len() as u64 * BITS_PER_SAMPLE as u64;
+
+    if bits + (order as u64 * BITS_PER_SAMPLE as u64) >= verbatim_bits {
+        out.write(0b0000_0010, 8);
+        for sample in samples {
+            write_signed(out, *sample);
+        }
+        return;
+    }
+
+    out.write(((0b001000 | order as u64) << 1) & 0x7e, 8);
+    for sample in &samples[..order] {
+        write_signed(out, *sample);
+    }
+    // Rice coding with 4-bit parameters in a single partition
+    out.write(0, 2);
+    out.write(0, 4);
+    out.write(parameter as u64, 4);
+    for residual in residuals {
+        let value = zigzag(residual);
+        out.write_unary(value >> parameter);
+        out.write(value & ((1 << parameter) - 1), parameter);
+    }
+}
+
+/// Residuals of the fixed predictor with the smallest total error
+fn best_fixed_prediction(samples: &[i32]) -> (usize, Vec<i64>) {
+    let max_order = MAX_FIXED_ORDER.min(samples.len() - 1);
+
+    (0..=max_order)
+        .map(|order| {
+            let residuals: Vec<i64> = (order..samples.len())
+                .map(|n| {
+                    let x = |back: usize| samples[n - back] as i64;
+                    match order {
+                        0 => x(0),
+                        1 => x(0) - x(1),
+                        2 => x(0) - 2 * x(1) + x(2),
+                        3 => x(0) - 3 * x(1) + 3 * x(2) - x(3),
+                        _ => x(0) - 4 * x(1) + 6 * x(2) - 4 * x(3) + x(4),
+                    }
+                })
+                .collect();
+            (order, residuals)
+        })
+        .min_by_key(|(_, residuals)| residuals.iter().map(|r| r.unsigned_abs()).sum::<u64>())
+        .expect("there is always a predictor of order 0")
+}
+
+/// Rice parameter that codes the residuals in the fewest bits, with that number of bits
+fn best_rice_parameter(residuals: &[i64]) -> (u32, u64) {
+    let bits = |parameter: u32| {
+        residuals
+            .iter()
+            .map(|residual| (zigzag(*residual) >> parameter) + 1 + parameter as u64)
+            .sum::<u64>()
+    };
+
+    // The best parameter is close to log2 of the mean, only its neighbours need checking
+    let sum: u64 = residuals.iter().map(|residual| zigzag(*residual)).sum();
+    let mean = sum / residuals.len().max(1) as u64;
+    let guess = (u64::BITS - mean.leading_zeros()).min(MAX_RICE_PARAMETER);
+
+    (guess.saturating_sub(1)..=(guess + 1).min(MAX_RICE_PARAMETER))
+        .map(|parameter| (parameter, bits(parameter)))
+        .min_by_key(|(_, bits)| *bits)
+        .expect("there is always a parameter to try")
+}
+
+/// Map signed residuals to unsigned ones: 0, -1, 1, -2, 2, ...
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn write_signed(out: &mut BitWriter, sample: i32) {
+    out.write(
+        sample as u64 & ((1 << BITS_PER_SAMPLE) - 1),
+        BITS_PER_SAMPLE,
+    );
+}
+
+/// Write a frame number in FLAC's UTF-8 like variable length coding
+fn write_coded_number(out: &mut BitWriter, value: u64) {
+    if value < 0x80 {
+        out.write(value, 8);
+        return;
+    }
+
+    // Each following byte holds 6 bits, the first byte what's left next to the length prefix
+    let bits = u64::BITS - value.leading_zeros();
+    let extra = (1..=6)
+        .find(|extra| bits <= 6 - extra + 6 * extra)
+        .unwrap_or(6);
+    let prefix = (0xff00_u64 >> (extra + 1)) & 0xff;
+    out.write(prefix | (value >> (6 * extra)), 8);
+    for byte in (0..extra).rev() {
+        out.write(0x80 | ((value >> (6 * byte)) & 0x3f), 8);
+    }
+}
+
+/// CRC-8 of frame headers (polynomial x^8 + x^2 + x + 1)
+fn crc8(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0, |mut crc, byte| {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+/// CRC-16 of whole frames (polynomial x^16 + x^15 + x^2 + 1)
+fn crc16(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0, |mut crc, byte| {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x8005
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+/// Collects bits most significant first
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    /// Bits that don't fill a byte yet, in the low `pending` bits
+    buffer: u64,
+    pending: u32,
+}
+
+impl BitWriter {
+    /// Append the low `bits` bits of `value` (at most 56 at a time)
+    fn write(&mut self, value: u64, bits: u32) {
+        debug_assert!(bits <= 56);
+        if bits == 0 {
+            return;
+        }
+
+        self.buffer = (self.buffer << bits) | (value & ((1 << bits) - 1));
+        self.pending += bits;
+        while self.pending >= 8 {
+            self.pending -= 8;
+            self.bytes.push((self.buffer >> self.pending) as u8);
+        }
+    }
+
+    /// Append `zeros` zero bits and a one
+    fn write_unary(&mut self, mut zeros: u64) {
+        while zeros > 32 {
+            self.write(0, 32);
+            zeros -= 32;
+        }
+        self.write(1, zeros as u32 + 1);
+    }
+
+    /// Fill the last byte with zero bits
+    fn align(&mut self) {
+        if self.pending > 0 {
+            self.write(0, 8 - self.pending);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Encode interleaved samples and decode them again
+    fn round_trip(channels: u16, samples: &[i16]) -> (claxon::metadata::StreamInfo, Vec<i32>) {
+        let mut file = Cursor::new(Vec::new());
+        let mut writer = FlacWriter::new(&mut file, channels, 44100).unwrap();
+        for sample in samples {
+            writer.write_sample(*sample).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        file.set_position(0);
+        let mut reader = claxon::FlacReader::new(file).unwrap();
+        let info = reader.streaminfo();
+        let decoded = reader.samples().map(|sample| sample.unwrap()).collect();
+        (info, decoded)
+    }
+
+    #[test]
+    fn round_trips_stereo_audio() {
+        // A few blocks of a tone, silence and noise, ending in a short block
+        let mut rng = fastrand::Rng::with_seed(7);
+        let samples: Vec<i16> = (0..(BLOCK_SIZE * 2 * 5 + 1234))
+            .map(|i| match i / (BLOCK_SIZE * 2) {
+                0 | 1 => ((i as f32 / 20.0).sin() * 20000.0) as i16,
+                2 => 0,
+                3 => rng.i16(..),
+                _ => [i16::MIN, i16::MAX][i % 2],
+            })
+            .collect();
+
+        let (info, decoded) = round_trip(2, &samples);
+        assert_eq!(info.channels, 2);
+        assert_eq!(info.sample_rate, 44100);
+        assert_eq!(info.bits_per_sample, 16);
+        assert_eq!(info.samples, Some(samples.len() as u64 / 2));
+        let expected: Vec<i32> = samples.iter().map(|sample| *sample as i32).collect();
+        assert_eq!(decoded, expected);
+    }
+
+    #[test]
+    fn round_trips_tiny_and_empty_streams() {
+        assert_eq!(round_trip(2, &[]).1, Vec::<i32>::new());
+        assert_eq!(round_trip(1, &[5]).1, [5]);
+        // The missing right sample is filled with silence
+        assert_eq!(round_trip(2, &[1, 2, 3]).1, [1, 2, 3, 0]);
+    }
+
+    #[test]
+    fn codes_large_frame_numbers() {
+        for (value, expected) in [
+            (0x7f, vec![0x7f]),
+            (0x80, vec![0xc2, 0x80]),
+            (0x7ff, vec![0xdf, 0xbf]),
+            (0x800, vec![0xe0, 0xa0, 0x80]),
+            (0x10000, vec![0xf0, 0x90, 0x80, 0x80]),
+        ] {
+            let mut out = BitWriter::default();
+            write_coded_number(&mut out, value);
+            assert_eq!(out.bytes, expected, "{:#x}", value);
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use rodio::Source;
+use std::f32::consts::FRAC_PI_2;
+use std::time::Duration;
+
+/// Source that loops a sound forever without a seam at the loop point.
+///
+/// The whole sound is decoded up front. The last `crossfade` of the sound is blended into the
+/// first `crossfade` using an equal-power curve, and the blended buffer is then played in a
+/// loop. Playing from the end of the buffer back into its start is therefore continuous.
+pub struct CrossfadeLoop {
+    samples: Vec<i16>,
+    position: usize,
+    channels: u16,
+    sample_rate: u32,
+}
+
+impl CrossfadeLoop {
+    /// Decode `source` and build a loop that overlaps its tail and head by `crossfade`.
+    /// The crossfade is limited to half the length of the sound.
+    pub fn new<S>(source: S, crossfade: Duration) -> Self
+    where
+        S: Source<Item = i16>,
+    {
+        let channels = source.channels().max(1);
+        let sample_rate = source.sample_rate();
+        let mut samples: Vec<i16> = source.collect();
+
+        // Drop any trailing partial frame so channels stay aligned when looping
+        let frame_len = channels as usize;
+        samples.truncate(samples.len() - samples.len() % frame_len);
+
+        let total_frames = samples.len() / frame_len;
+        let crossfade_frames =
+            ((crossfade.as_secs_f64() * sample_rate as f64) as usize).min(total_frames / 2);
+
+        if crossfade_frames > 0 {
+            let loop_frames = total_frames - crossfade_frames;
+
+            for frame in 0..crossfade_frames {
+                let t = (frame as f32 + 0.5) / crossfade_frames as f32;
+                let fade_in = (t * FRAC_PI_2).sin();
+                let fade_out = (t * FRAC_PI_2).cos();
+
+                for channel in 0..frame_len {
+                    let head = frame * frame_len + channel;
+                    let tail = (loop_frames + frame) * frame_len + channel;
+                    let mixed = samples[head] as f32 * fade_in + samples[tail] as f32 * fade_out;
+                    samples[head] = mixed.clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+                }
+            }
+
+            // The tail now lives in the blended head
+            samples.truncate(loop_frames * frame_len);
+        }
+
+        Self {
+            samples,
+            position: 0,
+            channels,
+            sample_rate,
+        }
+    }
+}
+
+impl Iterator for CrossfadeLoop {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        if self.samples.is_empty() {
+            return None;
+        }
+
+        let sample = self.samples[self.position];
+        self.position = (self.position + 1) % self.samples.len();
+        Some(sample)
+    }
+}
+
+impl Source for CrossfadeLoop {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    /// A mono 50 Hz sine of `frames` frames at 8 kHz, not ending on a full period
+    fn sine(frames: usize) -> SamplesBuffer<i16> {
+        let samples: Vec<i16> = (0..frames)
+            .map(|i| {
+                ((i as f32 * 50.0 / 8000.0 * 2.0 * std::f32::consts::PI).sin() * 10_000.0) as i16
+            })
+            .collect();
+        SamplesBuffer::new(1, 8000, samples)
+    }
+
+    #[test]
+    fn loop_is_shorter_by_the_crossfade() {
+        let looped = CrossfadeLoop::new(sine(1234), Duration::from_millis(50));
+        assert_eq!(looped.samples.len(), 1234 - 400);
+
+        // Plays the loop over and over
+        let played: Vec<i16> = looped.take(2 * 834).collect();
+        assert_eq!(played[..834], played[834..]);
+    }
+
+    #[test]
+    fn no_jump_at_the_loop_point() {
+        // Largest step between neighbouring samples of the sound itself
+        let source: Vec<i16> = sine(1234).collect();
+        let max_step = source
+            .windows(2)
+            .map(|pair| (pair[1] as i32 - pair[0] as i32).abs())
+            .max()
+            .unwrap();
+        // Without a crossfade the loop would jump
+        assert!((source[source.len() - 1] as i32 - source[0] as i32).abs() > 4 * max_step);
+
+        let looped = CrossfadeLoop::new(sine(1234), Duration::from_millis(50));
+        let last = looped.samples[looped.samples.len() - 1] as i32;
+        let first = looped.samples[0] as i32;
+        assert!(
+            (last - first).abs() <= 2 * max_step,
+            "{} -> {}",
+            last,
+            first
+        );
+    }
+
+    #[test]
+    fn clamps_overlong_crossfades() {
+        let looped = CrossfadeLoop::new(sine(100), Duration::from_secs(60));
+        assert_eq!(looped.samples.len(), 50);
+
+        // Stereo with a partial frame at the end
+        let stereo = SamplesBuffer::new(2, 8000, vec![1i16; 201]);
+        let looped = CrossfadeLoop::new(stereo, Duration::from_secs(60));
+        assert_eq!(looped.samples.len(), 100);
+
+        let mut empty = CrossfadeLoop::new(
+            SamplesBuffer::<i16>::new(1, 8000, vec![]),
+            Duration::from_secs(1),
+        );
+        assert_eq!(empty.next(), None);
+    }
+}
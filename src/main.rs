@@ -1,23 +1,233 @@
 mod app;
 mod audio;
+mod channels;
+mod checksums;
+mod cli;
 mod config;
+mod control;
+#[cfg(unix)]
+mod daemon;
 mod download;
+mod extract;
+mod filter;
+mod flac;
+mod http;
+mod looping;
+#[cfg(target_os = "linux")]
+mod mpris;
+mod noise;
+mod pan;
 mod prompt;
+mod ramp;
+mod scatter;
+mod sleep;
+mod soundpack;
 mod ui;
 
 use anyhow::Result;
 use app::App;
+use clap::Parser;
+use cli::{Cli, CliCommand};
+use config::{Config, Dirs};
+use std::path::Path;
 
 fn main() -> Result<()> {
-    let mut app = App::new()?;
+    let cli = Cli::parse();
 
     // Run the app
-    let result = app.run();
+    let result = run(cli);
 
     // Print any errors
     if let Err(e) = result {
-        eprintln!("Error: {}", e);
+        eprintln!("Error: {:#}", e);
+        std::process::exit(1);
     }
 
     Ok(())
 }
+
+fn run(cli: Cli) -> Result<()> {
+    let session = cli.session();
+
+    match cli.command {
+        Some(CliCommand::Devices) => list_devices(),
+        Some(CliCommand::Daemon) => run_daemon(session),
+        Some(CliCommand::Render {
+            preset,
+            duration,
+            out,
+        }) => {
+            let session = app::Session {
+                preset: preset.or(session.preset.clone()),
+                ..session
+            };
+            App::render_to_file(session, Dirs::locate()?, &out, duration)
+        }
+        Some(CliCommand::InstallSounds { path }) => install_sounds(&path),
+        Some(CliCommand::Packs { action }) => run_packs(action),
+        Some(CliCommand::Ctl { json, action }) => run_ctl(json, action),
+        None if cli.headless => App::new(session, Dirs::locate()?)?.run_headless(),
+        None => run_tui(session),
+    }
+}
+
+/// Print the available output devices, marking the default one
+fn list_devices() -> Result<()> {
+    let default = audio::default_output_device();
+
+    for device in audio::output_devices()? {
+        if default.as_ref() == Some(&device) {
+            println!("{} (default)", device);
+        } else {
+            println!("{}", device);
+        }
+    }
+
+    Ok(())
+}
+
+/// Install sounds from a local archive or folder and remember them as this version's sounds
+fn install_sounds(path: &Path) -> Result<()> {
+    let dirs = Dirs::locate()?;
+    let installed = download::install_sounds(path, &dirs.sounds)?;
+
+    // Without a pack manifest the version is unknown, but there's no reason to look for
+    // updates until the player is updated
+    let mut config = Config::load(&dirs.config_file)?;
+    config.sounds_version = installed.pack.as_ref().map(|pack| pack.version.to_string());
+    config.sounds_checked = Some(env!("CARGO_PKG_VERSION").to_string());
+    config.save(&dirs.config_file)?;
+
+    match &installed.pack {
+        Some(pack) => println!(
+            "Installed {} sound files (sound pack v{}) into {}",
+            installed.files,
+            pack.version,
+            dirs.sounds.display()
+        ),
+        None => println!(
+            "Installed {} sound files into {}",
+            installed.files,
+            dirs.sounds.display()
+        ),
+    }
+    Ok(())
+}
+
+/// List, install or remove sound packs
+fn run_packs(action: cli::PacksAction) -> Result<()> {
+    let dirs = Dirs::locate()?;
+
+    match action {
+        cli::PacksAction::List => list_packs(&dirs.sounds),
+        cli::PacksAction::Install { source } => {
+            let config = Config::load(&dirs.config_file)?;
+            let mut reporter = download::ConsoleReporter::default();
+            let pack =
+                download::install_pack(&mut reporter, &config.download, &source, &dirs.sounds)?;
+            let sounds = pack.sounds.len();
+            println!(
+                "Installed sound pack {} ({}) v{} with {} sound{}",
+                pack.name,
+                pack.id,
+                pack.version,
+                sounds,
+                if sounds == 1 { "" } else { "s" }
+            );
+            Ok(())
+        }
+        cli::PacksAction::Remove { id } => {
+            download::remove_pack(&id, &dirs.sounds)?;
+            println!("Removed sound pack {}", id);
+            Ok(())
+        }
+    }
+}
+
+/// Print the installed packs with their sounds, licenses and credits
+fn list_packs(sounds_dir: &Path) -> Result<()> {
+    for pack in soundpack::installed_packs(sounds_dir)? {
+        let missing = !pack.missing_files(&sounds_dir.join(pack.dir())).is_empty();
+        println!(
+            "{} ({}) v{}{}",
+            pack.name,
+            pack.id,
+            pack.version,
+            if missing { " (not downloaded)" } else { "" }
+        );
+
+        for sound in &pack.sounds {
+            let credit = sound
+                .credit
+                .as_ref()
+                .map(|credit| format!(", {}", credit))
+                .unwrap_or_default();
+            println!(
+                "  {:<24} {:>3}%  {}{}",
+                pack.channel_id(sound),
+                sound.volume,
+                sound.license,
+                credit
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Start the TUI, controlling the daemon if one is running
+#[cfg(unix)]
+fn run_tui(session: app::Session) -> Result<()> {
+    match daemon::Client::try_connect() {
+        Some(_)
+            if session.overrides_config()
+                || session.duration.is_some()
+                || session.device.is_some() =>
+        {
+            anyhow::bail!(
+            "A daemon is running. Use `relax-player ctl` to change its settings, or stop it first."
+        )
+        }
+        Some(mut client) => {
+            if let Some(name) = session.preset {
+                client.send(&control::Command::LoadPreset { name })?;
+            }
+            App::connect(client, Dirs::locate()?)?.run()
+        }
+        None => App::new(session, Dirs::locate()?)?.run(),
+    }
+}
+
+#[cfg(not(unix))]
+fn run_tui(session: app::Session) -> Result<()> {
+    App::new(session, Dirs::locate()?)?.run()
+}
+
+#[cfg(unix)]
+fn run_daemon(session: app::Session) -> Result<()> {
+    App::new(session, Dirs::locate()?)?.run_daemon()
+}
+
+#[cfg(not(unix))]
+fn run_daemon(_session: app::Session) -> Result<()> {
+    anyhow::bail!("Daemon mode is only supported on Unix-like systems")
+}
+
+#[cfg(unix)]
+fn run_ctl(json: bool, action: cli::CtlAction) -> Result<()> {
+    let command = action.into_command().map_err(anyhow::Error::msg)?;
+    let status = daemon::Client::connect()?.send(&command)?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&status)?);
+    } else {
+        cli::print_status(&status);
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn run_ctl(_json: bool, _action: cli::CtlAction) -> Result<()> {
+    anyhow::bail!("Daemon mode is only supported on Unix-like systems")
+}
@@ -0,0 +1,401 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use zbus::blocking::connection;
+use zbus::blocking::Connection;
+use zbus::interface;
+use zbus::zvariant::{ObjectPath, OwnedValue, Value};
+
+use crate::config::SoundConfig;
+use crate::control::{self, Command};
+
+/// Well-known bus name the player is published under
+pub const BUS_NAME: &str = "org.mpris.MediaPlayer2.relax_player";
+
+/// Object path required by the MPRIS specification
+pub const OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+
+/// Master volume state shown to MPRIS clients
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct MasterState {
+    volume: u8,
+    muted: bool,
+}
+
+impl From<&SoundConfig> for MasterState {
+    fn from(master: &SoundConfig) -> Self {
+        Self {
+            volume: master.volume,
+            muted: master.muted,
+        }
+    }
+}
+
+/// Publishes the player on D-Bus as an MPRIS2 media player.
+///
+/// Desktop media keys and tools like playerctl control the master channel: play/pause toggles
+/// the master mute and the MPRIS volume is the master volume. Incoming calls are queued as
+/// commands for the app to apply, so the app stays the only owner of the config.
+pub struct MprisServer {
+    connection: Connection,
+    commands: Receiver<Command>,
+    state: Arc<Mutex<MasterState>>,
+    published: MasterState,
+}
+
+impl MprisServer {
+    /// Publish the player on the session bus
+    pub fn start(master: &SoundConfig) -> Result<Self> {
+        let builder = connection::Builder::session().context("Failed to connect to session bus")?;
+        Self::start_with(builder, master)
+    }
+
+    /// Publish the player on the bus the builder connects to (e.g. a private bus for tests)
+    pub fn start_with(builder: connection::Builder, master: &SoundConfig) -> Result<Self> {
+        let (sender, commands) = mpsc::channel();
+        let state = Arc::new(Mutex::new(MasterState::from(master)));
+
+        let connection = builder
+            .name(BUS_NAME)?
+            .serve_at(
+                OBJECT_PATH,
+                Root {
+                    commands: sender.clone(),
+                },
+            )?
+            .serve_at(
+                OBJECT_PATH,
+                Player {
+                    commands: sender,
+                    state: Arc::clone(&state),
+                },
+            )?
+            .build()
+            .context("Failed to publish MPRIS interface")?;
+
+        Ok(Self {
+            connection,
+            commands,
+            published: MasterState::from(master),
+            state,
+        })
+    }
+
+    /// Get the next command received from a media player client, if any
+    pub fn try_recv(&self) -> Option<Command> {
+        self.commands.try_recv().ok()
+    }
+
+    /// Report the current master state, notifying clients if it changed
+    pub fn update(&mut self, master: &SoundConfig) -> Result<()> {
+        let current = MasterState::from(master);
+        *self.state.lock().unwrap() = current;
+
+        if current == self.published {
+            return Ok(());
+        }
+
+        let iface_ref = self
+            .connection
+            .object_server()
+            .interface::<_, Player>(OBJECT_PATH)?;
+        let iface = iface_ref.get();
+        let emitter = iface_ref.signal_emitter();
+
+        if current.volume != self.published.volume {
+            zbus::block_on(iface.volume_changed(emitter))?;
+        }
+        if current.muted != self.published.muted {
+            zbus::block_on(iface.playback_status_changed(emitter))?;
+        }
+
+        self.published = current;
+        Ok(())
+    }
+}
+
+/// The org.mpris.MediaPlayer2 root interface
+struct Root {
+    commands: Sender<Command>,
+}
+
+#[interface(name = "org.mpris.MediaPlayer2")]
+impl Root {
+    fn raise(&self) {}
+
+    fn quit(&self) {
+        let _ = self.commands.send(Command::Stop);
+    }
+
+    #[zbus(property)]
+    fn can_quit(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_raise(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn has_track_list(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn identity(&self) -> &str {
+        "Relax Player"
+    }
+
+    #[zbus(property)]
+    fn supported_uri_schemes(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    #[zbus(property)]
+    fn supported_mime_types(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// The org.mpris.MediaPlayer2.Player interface, mapped onto the master channel
+struct Player {
+    commands: Sender<Command>,
+    state: Arc<Mutex<MasterState>>,
+}
+
+impl Player {
+    fn set_master_muted(&self, muted: bool) {
+        let _ = self.commands.send(Command::SetMute {
+            channel: control::MASTER.to_string(),
+            muted,
+        });
+    }
+}
+
+#[interface(name = "org.mpris.MediaPlayer2.Player")]
+impl Player {
+    fn play_pause(&self) {
+        let _ = self.commands.send(Command::ToggleMute {
+            channel: control::MASTER.to_string(),
+        });
+    }
+
+    fn play(&self) {
+        self.set_master_muted(false);
+    }
+
+    fn pause(&self) {
+        self.set_master_muted(true);
+    }
+
+    fn stop(&self) {
+        self.set_master_muted(true);
+    }
+
+    // Ambient sounds have no tracks or positions, so these do nothing
+    fn next(&self) {}
+
+    fn previous(&self) {}
+
+    fn seek(&self, _offset: i64) {}
+
+    fn set_position(&self, _track_id: ObjectPath<'_>, _position: i64) {}
+
+    fn open_uri(&self, _uri: &str) {}
+
+    #[zbus(property)]
+    fn playback_status(&self) -> &str {
+        if self.state.lock().unwrap().muted {
+            "Paused"
+        } else {
+            "Playing"
+        }
+    }
+
+    #[zbus(property)]
+    fn volume(&self) -> f64 {
+        self.state.lock().unwrap().volume as f64 / 100.0
+    }
+
+    #[zbus(property)]
+    fn set_volume(&mut self, volume: f64) {
+        let volume = (volume.clamp(0.0, 1.0) * 100.0).round() as u8;
+        self.state.lock().unwrap().volume = volume;
+
+        let _ = self.commands.send(Command::SetVolume {
+            channel: control::MASTER.to_string(),
+            volume,
+        });
+    }
+
+    #[zbus(property)]
+    fn metadata(&self) -> HashMap<String, OwnedValue> {
+        let mut metadata = HashMap::new();
+        if let Ok(track_id) = ObjectPath::try_from("/org/mpris/MediaPlayer2/TrackList/NoTrack")
+            .map(Value::from)
+            .and_then(OwnedValue::try_from)
+        {
+            metadata.insert("mpris:trackid".to_string(), track_id);
+        }
+        if let Ok(title) = OwnedValue::try_from(Value::from("Relax Player")) {
+            metadata.insert("xesam:title".to_string(), title);
+        }
+        metadata
+    }
+
+    #[zbus(property)]
+    fn rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn minimum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn maximum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn position(&self) -> i64 {
+        0
+    }
+
+    #[zbus(property)]
+    fn can_go_next(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn can_go_previous(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn can_play(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_pause(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_seek(&self) -> bool {
+        false
+    }
+
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn can_control(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command as Process, Stdio};
+    use zbus::blocking::proxy;
+    use zbus::proxy::CacheProperties;
+
+    const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+
+    /// A private session bus, shut down when dropped
+    struct PrivateBus {
+        daemon: Child,
+        address: String,
+    }
+
+    impl PrivateBus {
+        /// Start a dbus-daemon for this test, or None if it isn't installed
+        fn start() -> Option<Self> {
+            let mut daemon = Process::new("dbus-daemon")
+                .args(["--session", "--nofork", "--print-address"])
+                .stdout(Stdio::piped())
+                .spawn()
+                .ok()?;
+
+            let mut address = String::new();
+            BufReader::new(daemon.stdout.take()?)
+                .read_line(&mut address)
+                .ok()?;
+
+            Some(Self {
+                daemon,
+                address: address.trim().to_string(),
+            })
+        }
+
+        fn connect(&self) -> connection::Builder<'static> {
+            connection::Builder::address(self.address.as_str()).unwrap()
+        }
+    }
+
+    impl Drop for PrivateBus {
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+            let _ = self.daemon.wait();
+        }
+    }
+
+    #[test]
+    fn media_controls_map_to_master_channel() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not found, skipping MPRIS test");
+            return;
+        };
+
+        let mut master = SoundConfig {
+            volume: 60,
+            ..SoundConfig::default()
+        };
+        let mut server = MprisServer::start_with(bus.connect(), &master).unwrap();
+
+        let client = bus.connect().build().unwrap();
+        let player = proxy::Builder::<zbus::blocking::Proxy>::new(&client)
+            .destination(BUS_NAME)
+            .unwrap()
+            .path(OBJECT_PATH)
+            .unwrap()
+            .interface(PLAYER_INTERFACE)
+            .unwrap()
+            .cache_properties(CacheProperties::No)
+            .build()
+            .unwrap();
+
+        let volume: f64 = player.get_property("Volume").unwrap();
+        assert_eq!(volume, 0.6);
+        let status: String = player.get_property("PlaybackStatus").unwrap();
+        assert_eq!(status, "Playing");
+
+        // Play/pause toggles the master mute
+        player.call_method("PlayPause", &()).unwrap();
+        assert!(matches!(
+            server.try_recv(),
+            Some(Command::ToggleMute { channel }) if channel == control::MASTER
+        ));
+
+        // Volume sets the master volume
+        player.set_property("Volume", 0.25).unwrap();
+        assert!(matches!(
+            server.try_recv(),
+            Some(Command::SetVolume { channel, volume: 25 }) if channel == control::MASTER
+        ));
+
+        // Changes made by the app are visible to clients
+        master.volume = 25;
+        master.muted = true;
+        server.update(&master).unwrap();
+
+        let status: String = player.get_property("PlaybackStatus").unwrap();
+        assert_eq!(status, "Paused");
+        let volume: f64 = player.get_property("Volume").unwrap();
+        assert_eq!(volume, 0.25);
+    }
+}
@@ -0,0 +1,165 @@
+use rodio::Source;
+use std::time::Duration;
+
+const SAMPLE_RATE: u32 = 44100;
+const CHANNELS: u16 = 2;
+
+/// Spectral colour of a generated noise
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoiseColor {
+    /// Equal energy at all frequencies
+    White,
+    /// Energy falls off 3 dB per octave, softer than white
+    Pink,
+    /// Energy falls off 6 dB per octave, a deep rumble
+    Brown,
+}
+
+/// Per-channel filter state so left and right are independent (decorrelated) noise
+#[derive(Default, Clone, Copy)]
+struct FilterState {
+    pink: [f32; 7],
+    brown: f32,
+}
+
+/// Endless stereo noise generator
+pub struct Noise {
+    color: NoiseColor,
+    rng: fastrand::Rng,
+    state: [FilterState; CHANNELS as usize],
+    channel: usize,
+}
+
+impl Noise {
+    pub fn new(color: NoiseColor) -> Self {
+        Self {
+            color,
+            rng: fastrand::Rng::new(),
+            state: [FilterState::default(); CHANNELS as usize],
+            channel: 0,
+        }
+    }
+
+    /// Uniform white noise in -1.0..1.0
+    fn white(&mut self) -> f32 {
+        self.rng.f32() * 2.0 - 1.0
+    }
+}
+
+impl Iterator for Noise {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let white = self.white();
+        let state = &mut self.state[self.channel];
+        self.channel = (self.channel + 1) % CHANNELS as usize;
+
+        let sample = match self.color {
+            NoiseColor::White => white * 0.3,
+            NoiseColor::Pink => {
+                // Paul Kellet's refined pink noise filter
+                let b = &mut state.pink;
+                b[0] = 0.99886 * b[0] + white * 0.0555179;
+                b[1] = 0.99332 * b[1] + white * 0.0750759;
+                b[2] = 0.96900 * b[2] + white * 0.153852;
+                b[3] = 0.86650 * b[3] + white * 0.3104856;
+                b[4] = 0.55000 * b[4] + white * 0.5329522;
+                b[5] = -0.7616 * b[5] - white * 0.0168980;
+                let pink = b.iter().sum::<f32>() + white * 0.5362;
+                b[6] = white * 0.115926;
+                pink * 0.11
+            }
+            NoiseColor::Brown => {
+                // Leaky integration of white noise
+                state.brown = (state.brown + 0.02 * white) / 1.02;
+                state.brown * 3.5
+            }
+        };
+
+        Some(sample.clamp(-1.0, 1.0))
+    }
+}
+
+impl Source for Noise {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        CHANNELS
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COLORS: [NoiseColor; 3] = [NoiseColor::White, NoiseColor::Pink, NoiseColor::Brown];
+
+    #[test]
+    fn stays_in_range_without_clipping() {
+        // A minute, far longer than brown noise's integrator needs to drift if it were going to
+        let samples = SAMPLE_RATE as usize * CHANNELS as usize * 60;
+
+        for color in COLORS {
+            let mut clipped = 0;
+            let mut sum = 0.0;
+            let mut squares = 0.0;
+            for sample in Noise::new(color).take(samples) {
+                assert!((-1.0..=1.0).contains(&sample), "{:?}: {}", color, sample);
+                if sample.abs() >= 1.0 {
+                    clipped += 1;
+                }
+                sum += sample as f64;
+                squares += (sample * sample) as f64;
+            }
+
+            let mean = sum / samples as f64;
+            let rms = (squares / samples as f64).sqrt();
+            assert!(
+                clipped < samples / 10_000,
+                "{:?}: {} clipped",
+                color,
+                clipped
+            );
+            assert!(mean.abs() < 0.01, "{:?}: mean {}", color, mean);
+            assert!((0.05..0.5).contains(&rms), "{:?}: rms {}", color, rms);
+        }
+    }
+
+    #[test]
+    fn is_endless_stereo_at_the_stated_rate() {
+        for color in COLORS {
+            let noise = Noise::new(color);
+            assert_eq!(noise.channels(), 2);
+            assert_eq!(noise.sample_rate(), 44100);
+            assert_eq!(noise.total_duration(), None);
+            assert_eq!(noise.current_frame_len(), None);
+            assert_eq!(noise.take(SAMPLE_RATE as usize * 10).count(), 441_000);
+        }
+    }
+
+    #[test]
+    fn left_and_right_differ() {
+        for color in COLORS {
+            let samples: Vec<f32> = Noise::new(color).take(200_000).collect();
+            let (mut both, mut left, mut right) = (0.0, 0.0, 0.0);
+            for frame in samples.chunks(2) {
+                both += (frame[0] * frame[1]) as f64;
+                left += (frame[0] * frame[0]) as f64;
+                right += (frame[1] * frame[1]) as f64;
+            }
+
+            let correlation = both / (left * right).sqrt();
+            assert!(correlation.abs() < 0.1, "{:?}: {}", color, correlation);
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use rodio::Source;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How quickly the pan follows a new position, per frame.
+/// Small enough that moving the pan never clicks, fast enough to feel immediate.
+const PAN_SMOOTHING: f32 = 0.001;
+
+/// Describe a pan value for display, e.g. "L30", "C" or "R100"
+pub fn format_pan(pan: i8) -> String {
+    match pan {
+        0 => "C".to_string(),
+        pan if pan < 0 => format!("L{}", pan.unsigned_abs()),
+        pan => format!("R{}", pan),
+    }
+}
+
+/// Stereo position of a channel, shared between the player and the channel's audio source
+#[derive(Debug, Clone, Default)]
+pub struct PanControl(Arc<AtomicU32>);
+
+impl PanControl {
+    /// Set the position from -1.0 (left) to 1.0 (right)
+    pub fn set(&self, pan: f32) {
+        self.0
+            .store(pan.clamp(-1.0, 1.0).to_bits(), Ordering::Relaxed);
+    }
+
+    fn get(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+}
+
+/// Source that places a sound in the stereo field.
+///
+/// The output is always stereo: mono sources are spread to both sides and sources with more
+/// than two channels keep only the first two. Panning works like a balance control, the far
+/// side is turned down while the near side stays at full volume, so a centered channel sounds
+/// exactly like the original.
+pub struct Panned<S> {
+    source: S,
+    control: PanControl,
+    pan: f32,
+    /// Right sample of the current frame, returned after the left one
+    pending_right: Option<f32>,
+}
+
+impl<S> Panned<S>
+where
+    S: Source<Item = f32>,
+{
+    pub fn new(source: S, control: PanControl) -> Self {
+        let pan = control.get();
+        Self {
+            source,
+            control,
+            pan,
+            pending_right: None,
+        }
+    }
+}
+
+impl<S> Iterator for Panned<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if let Some(right) = self.pending_right.take() {
+            return Some(right);
+        }
+
+        // Read one whole frame from the source
+        let channels = self.source.channels().max(1);
+        let left = self.source.next()?;
+        let right = if channels > 1 {
+            self.source.next().unwrap_or(left)
+        } else {
+            left
+        };
+        for _ in 2..channels {
+            self.source.next();
+        }
+
+        self.pan += (self.control.get() - self.pan) * PAN_SMOOTHING;
+        let left_gain = (1.0 - self.pan).min(1.0);
+        let right_gain = (1.0 + self.pan).min(1.0);
+
+        self.pending_right = Some(right * right_gain);
+        Some(left * left_gain)
+    }
+}
+
+impl<S> Source for Panned<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        let channels = self.source.channels().max(1) as usize;
+        let pending = self.pending_right.map_or(0, |_| 1);
+        self.source
+            .current_frame_len()
+            .map(|len| len / channels * 2 + pending)
+    }
+
+    fn channels(&self) -> u16 {
+        2
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    /// Pan a full-scale source with `channels` channels to `pan`, returning its output frames
+    fn panned(channels: u16, pan: f32) -> Vec<(f32, f32)> {
+        let control = PanControl::default();
+        control.set(pan);
+        let source = SamplesBuffer::new(channels, 1000, vec![1.0; channels as usize * 10]);
+
+        let samples: Vec<f32> = Panned::new(source, control).collect();
+        samples
+            .chunks(2)
+            .map(|frame| (frame[0], frame[1]))
+            .collect()
+    }
+
+    #[test]
+    fn pans_like_a_balance_control() {
+        assert_eq!(panned(2, -1.0), vec![(1.0, 0.0); 10]);
+        assert_eq!(panned(2, 0.0), vec![(1.0, 1.0); 10]);
+        assert_eq!(panned(2, 1.0), vec![(0.0, 1.0); 10]);
+        assert_eq!(panned(2, -0.5), vec![(1.0, 0.5); 10]);
+        assert_eq!(panned(2, 0.25), vec![(0.75, 1.0); 10]);
+
+        // Out of range positions are held at the sides
+        assert_eq!(panned(2, -3.0), vec![(1.0, 0.0); 10]);
+    }
+
+    #[test]
+    fn spreads_mono_to_both_sides() {
+        assert_eq!(panned(1, 0.0), vec![(1.0, 1.0); 10]);
+        assert_eq!(panned(1, 1.0), vec![(0.0, 1.0); 10]);
+
+        let source = SamplesBuffer::new(1, 1000, vec![0.5; 10]);
+        assert_eq!(Panned::new(source, PanControl::default()).channels(), 2);
+    }
+
+    #[test]
+    fn keeps_the_first_two_of_more_channels() {
+        let source = SamplesBuffer::new(3, 1000, vec![0.1, 0.2, 0.3, 0.4, 0.5, 0.6]);
+        let samples: Vec<f32> = Panned::new(source, PanControl::default()).collect();
+        assert_eq!(samples, vec![0.1, 0.2, 0.4, 0.5]);
+    }
+
+    #[test]
+    fn moves_smoothly_to_a_new_position() {
+        let control = PanControl::default();
+        let source = SamplesBuffer::new(2, 1000, vec![1.0; 20_000]);
+        let mut panned = Panned::new(source, control.clone());
+
+        control.set(1.0);
+        let lefts: Vec<f32> = panned.by_ref().step_by(2).take(5000).collect();
+        assert!(lefts[0] > 0.99);
+        assert!(lefts.windows(2).all(|pair| pair[1] <= pair[0]));
+        assert!(lefts.windows(2).all(|pair| pair[0] - pair[1] < 0.01));
+        assert!(*lefts.last().unwrap() < 0.01);
+    }
+
+    #[test]
+    fn formats_pan() {
+        assert_eq!(format_pan(0), "C");
+        assert_eq!(format_pan(-30), "L30");
+        assert_eq!(format_pan(-128), "L128");
+        assert_eq!(format_pan(100), "R100");
+    }
+}
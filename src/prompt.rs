@@ -1,15 +1,19 @@
 use anyhow::Result;
-use crossterm::event::{self, Event, KeyCode};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
 use ratatui::{
-    layout::{Alignment, Constraint, Direction, Layout},
+    layout::{Alignment, Constraint, Direction, Layout, Margin},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, Paragraph},
+    widgets::{Block, Borders, Gauge, Paragraph},
     DefaultTerminal,
 };
 
 pub enum PromptType {
-    YesNo,
+    /// Ask a question, `yes` and `no` say what the keys do, e.g. "download" and "skip"
+    YesNo {
+        yes: &'static str,
+        no: &'static str,
+    },
     Info,
     Error,
 }
@@ -47,7 +51,7 @@ pub fn run_prompt(
             )),
             Line::from(""),
             match prompt_type {
-                PromptType::YesNo => Line::from(vec![
+                PromptType::YesNo { yes, no } => Line::from(vec![
                     Span::styled("Press ", Style::default()),
                     Span::styled(
                         "y",
@@ -55,12 +59,12 @@ pub fn run_prompt(
                             .fg(Color::Green)
                             .add_modifier(Modifier::BOLD),
                     ),
-                    Span::styled(" to download or ", Style::default()),
+                    Span::styled(format!(" to {} or ", yes), Style::default()),
                     Span::styled(
                         "n",
                         Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
                     ),
-                    Span::styled(" to skip", Style::default()),
+                    Span::styled(format!(" to {}", no), Style::default()),
                 ]),
                 PromptType::Info => Line::from(""),
                 PromptType::Error => Line::from(vec![
@@ -69,7 +73,7 @@ pub fn run_prompt(
                         "enter",
                         Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
                     ),
-                    Span::styled(" to exit.", Style::default()),
+                    Span::styled(" to continue.", Style::default()),
                 ]),
             },
         ];
@@ -93,7 +97,7 @@ pub fn run_prompt(
 
         if let Event::Key(key) = event::read()? {
             match prompt_type {
-                PromptType::YesNo => match key.code {
+                PromptType::YesNo { .. } => match key.code {
                     KeyCode::Char('y') | KeyCode::Char('Y') => return Ok(true),
                     KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => return Ok(false),
                     _ => {}
@@ -108,3 +112,149 @@ pub fn run_prompt(
         }
     }
 }
+
+/// Draw a progress bar filled to `ratio` (0.0 to 1.0), with a line of details below it.
+/// Returns immediately, the caller redraws it as progress is made.
+pub fn draw_progress(
+    terminal: &mut DefaultTerminal,
+    title: &str,
+    ratio: f64,
+    label: &str,
+    details: &str,
+) -> Result<()> {
+    terminal.draw(|f| {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Percentage(40),
+                Constraint::Min(7),
+                Constraint::Percentage(40),
+            ])
+            .split(f.area());
+
+        let block = Block::default()
+            .title(title)
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::Cyan));
+        let inner = block.inner(chunks[1]);
+        f.render_widget(block, chunks[1]);
+
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(1),
+                Constraint::Length(1),
+                Constraint::Length(1),
+                Constraint::Length(1),
+                Constraint::Min(0),
+            ])
+            .split(inner);
+
+        let gauge = Gauge::default()
+            .gauge_style(Style::default().fg(Color::Green))
+            .ratio(ratio.clamp(0.0, 1.0))
+            .label(label);
+        f.render_widget(gauge, rows[1].inner(Margin::new(2, 0)));
+
+        let details = Paragraph::new(Line::from(Span::styled(
+            details,
+            Style::default().add_modifier(Modifier::BOLD),
+        )))
+        .alignment(Alignment::Center);
+        f.render_widget(details, rows[2]);
+
+        let hint = Paragraph::new(Line::from(vec![
+            Span::styled("Press ", Style::default()),
+            Span::styled(
+                "esc",
+                Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            ),
+            Span::styled(" to cancel", Style::default()),
+        ]))
+        .alignment(Alignment::Center);
+        f.render_widget(hint, rows[3]);
+    })?;
+
+    Ok(())
+}
+
+/// Ask for a line of text, starting from `initial`.
+/// Returns None if the prompt was cancelled with Esc or left empty.
+pub fn run_text_prompt(
+    terminal: &mut DefaultTerminal,
+    title: &str,
+    message: &str,
+    initial: &str,
+) -> Result<Option<String>> {
+    let mut input = initial.to_string();
+
+    loop {
+        terminal.draw(|f| {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([
+                    Constraint::Percentage(40),
+                    Constraint::Min(7),
+                    Constraint::Percentage(40),
+                ])
+                .split(f.area());
+
+            let block = Block::default()
+                .title(title)
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::Cyan));
+
+            let text = vec![
+                Line::from(""),
+                Line::from(Span::styled(
+                    message,
+                    Style::default().add_modifier(Modifier::BOLD),
+                )),
+                Line::from(vec![
+                    Span::styled(input.as_str(), Style::default().fg(Color::Yellow)),
+                    Span::styled("█", Style::default().fg(Color::Yellow)),
+                ]),
+                Line::from(vec![
+                    Span::styled("Press ", Style::default()),
+                    Span::styled(
+                        "enter",
+                        Style::default()
+                            .fg(Color::Green)
+                            .add_modifier(Modifier::BOLD),
+                    ),
+                    Span::styled(" to confirm or ", Style::default()),
+                    Span::styled(
+                        "esc",
+                        Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+                    ),
+                    Span::styled(" to cancel", Style::default()),
+                ]),
+            ];
+
+            let paragraph = Paragraph::new(text)
+                .block(block)
+                .alignment(Alignment::Center);
+
+            f.render_widget(paragraph, chunks[1]);
+        })?;
+
+        if let Event::Key(key) = event::read()? {
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+
+            match key.code {
+                KeyCode::Enter => {
+                    let input = input.trim();
+                    return Ok((!input.is_empty()).then(|| input.to_string()));
+                }
+                KeyCode::Esc => return Ok(None),
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Char(c) => input.push(c),
+                _ => {}
+            }
+        }
+    }
+}
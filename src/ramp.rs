@@ -0,0 +1,202 @@
+use rodio::Source;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Target volume of a channel, shared between the player and the channel's audio source.
+/// Setting a new volume starts a ramp from whatever the source is playing at right now.
+#[derive(Debug, Clone, Default)]
+pub struct VolumeControl(Arc<Target>);
+
+#[derive(Debug, Default)]
+struct Target {
+    /// Volume as f32 bits, so it can be shared without locking the audio thread
+    volume: AtomicU32,
+    ramp_ms: AtomicU32,
+}
+
+impl VolumeControl {
+    /// Move to a new volume (0.0 to 1.0) over `ramp`, or immediately for a zero duration
+    pub fn set(&self, volume: f32, ramp: Duration) {
+        let ramp_ms = ramp.as_millis().min(u32::MAX as u128) as u32;
+        self.0.ramp_ms.store(ramp_ms, Ordering::Relaxed);
+        self.0.volume.store(volume.to_bits(), Ordering::Relaxed);
+    }
+
+    fn volume(&self) -> f32 {
+        f32::from_bits(self.0.volume.load(Ordering::Relaxed))
+    }
+
+    fn ramp_ms(&self) -> u32 {
+        self.0.ramp_ms.load(Ordering::Relaxed)
+    }
+}
+
+/// Source that applies the volume of a `VolumeControl`, ramping linearly between volumes
+/// so changes never jump abruptly.
+pub struct Ramped<S> {
+    source: S,
+    control: VolumeControl,
+    gain: f32,
+    target: f32,
+    /// Gain change per frame while ramping
+    step: f32,
+    frames_remaining: u32,
+    /// Channel of the next sample within its frame
+    channel: u16,
+}
+
+impl<S> Ramped<S>
+where
+    S: Source<Item = f32>,
+{
+    /// Wrap a source, starting silent until the control is given a volume
+    pub fn new(source: S, control: VolumeControl) -> Self {
+        Self {
+            source,
+            control,
+            gain: 0.0,
+            target: 0.0,
+            step: 0.0,
+            frames_remaining: 0,
+            channel: 0,
+        }
+    }
+
+    /// Pick up a new target volume and advance the ramp by one frame
+    fn update_gain(&mut self) {
+        let target = self.control.volume();
+        if target != self.target {
+            self.target = target;
+
+            let frames = self.control.ramp_ms() as u64 * self.source.sample_rate() as u64 / 1000;
+            self.frames_remaining = frames.min(u32::MAX as u64) as u32;
+            if self.frames_remaining == 0 {
+                self.gain = target;
+            } else {
+                self.step = (target - self.gain) / self.frames_remaining as f32;
+            }
+        }
+
+        if self.frames_remaining > 0 {
+            self.frames_remaining -= 1;
+            self.gain = if self.frames_remaining == 0 {
+                self.target
+            } else {
+                self.gain + self.step
+            };
+        }
+    }
+}
+
+impl<S> Iterator for Ramped<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        // Keep the gain the same for all channels of a frame
+        if self.channel == 0 {
+            self.update_gain();
+        }
+
+        let sample = self.source.next()?;
+        self.channel = (self.channel + 1) % self.source.channels().max(1);
+
+        Some(sample * self.gain)
+    }
+}
+
+impl<S> Source for Ramped<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    /// Stereo source at 1 kHz playing full scale, so its output is the gain of each sample
+    fn ramped(control: &VolumeControl) -> Ramped<SamplesBuffer<f32>> {
+        Ramped::new(
+            SamplesBuffer::new(2, 1000, vec![1.0; 20_000]),
+            control.clone(),
+        )
+    }
+
+    /// Take `count` frames, checking both channels of each play at the same gain
+    fn frames(source: &mut impl Iterator<Item = f32>, count: usize) -> Vec<f32> {
+        (0..count)
+            .map(|_| {
+                let (left, right) = (source.next().unwrap(), source.next().unwrap());
+                assert_eq!(left, right);
+                left
+            })
+            .collect()
+    }
+
+    fn assert_linear(gains: &[f32], from: f32, to: f32) {
+        let step = (to - from) / gains.len() as f32;
+        for (frame, gain) in gains.iter().enumerate() {
+            let expected = from + step * (frame + 1) as f32;
+            assert!((gain - expected).abs() < 1e-4, "frame {}: {}", frame, gain);
+        }
+        assert_eq!(*gains.last().unwrap(), to);
+    }
+
+    #[test]
+    fn ramps_linearly_over_the_configured_time() {
+        let control = VolumeControl::default();
+        let mut source = ramped(&control);
+        assert_eq!(frames(&mut source, 10), vec![0.0; 10]);
+
+        // 100 ms at 1 kHz is 100 frames up to full volume, then it stays there
+        control.set(1.0, Duration::from_millis(100));
+        assert_linear(&frames(&mut source, 100), 0.0, 1.0);
+        assert_eq!(frames(&mut source, 10), vec![1.0; 10]);
+
+        control.set(0.5, Duration::from_millis(200));
+        assert_linear(&frames(&mut source, 200), 1.0, 0.5);
+        assert_eq!(frames(&mut source, 10), vec![0.5; 10]);
+    }
+
+    #[test]
+    fn new_volume_ramps_from_where_the_last_one_got_to() {
+        let control = VolumeControl::default();
+        let mut source = ramped(&control);
+
+        control.set(1.0, Duration::from_millis(100));
+        let halfway = *frames(&mut source, 50).last().unwrap();
+        assert!((halfway - 0.5).abs() < 1e-4);
+
+        control.set(0.0, Duration::from_millis(100));
+        assert_linear(&frames(&mut source, 100), halfway, 0.0);
+    }
+
+    #[test]
+    fn zero_duration_jumps() {
+        let control = VolumeControl::default();
+        let mut source = ramped(&control);
+
+        control.set(0.8, Duration::ZERO);
+        assert_eq!(frames(&mut source, 5), vec![0.8; 5]);
+    }
+}
@@ -0,0 +1,268 @@
+use rodio::source::UniformSourceIterator;
+use rodio::Source;
+use std::time::Duration;
+
+/// Source that plays short one-shot samples (e.g. thunder strikes) separated by random gaps.
+///
+/// Each strike is picked at random from the loaded samples and played with a random gain, so
+/// over a long session the sound never settles into a predictable pattern. Silence is emitted
+/// between strikes, which keeps the source running forever.
+pub struct RandomOneShots {
+    strikes: Vec<Vec<i16>>,
+    channels: u16,
+    sample_rate: u32,
+    min_gap: Duration,
+    max_gap: Duration,
+    intensity_variance: f32,
+    /// Strike currently playing as (index, position, gain)
+    current: Option<(usize, usize, f32)>,
+    silence_remaining: usize,
+    rng: fastrand::Rng,
+}
+
+impl RandomOneShots {
+    /// Create a scheduler from decoded strike samples.
+    /// All samples are converted to the channel count and sample rate of the first one.
+    /// `intensity_variance` is how much quieter (0.0 to 1.0) a strike can randomly be played.
+    pub fn new<S>(
+        sources: Vec<S>,
+        min_gap: Duration,
+        max_gap: Duration,
+        intensity_variance: f32,
+    ) -> Self
+    where
+        S: Source<Item = i16>,
+    {
+        Self::with_rng(
+            sources,
+            min_gap,
+            max_gap,
+            intensity_variance,
+            fastrand::Rng::new(),
+        )
+    }
+
+    /// Create a scheduler that draws its gaps, strikes and gains from `rng`
+    fn with_rng<S>(
+        sources: Vec<S>,
+        min_gap: Duration,
+        max_gap: Duration,
+        intensity_variance: f32,
+        rng: fastrand::Rng,
+    ) -> Self
+    where
+        S: Source<Item = i16>,
+    {
+        let channels = sources.first().map_or(2, |s| s.channels().max(1));
+        let sample_rate = sources.first().map_or(44100, |s| s.sample_rate());
+
+        let strikes = sources
+            .into_iter()
+            .map(|source| {
+                UniformSourceIterator::<S, i16>::new(source, channels, sample_rate).collect()
+            })
+            .filter(|samples: &Vec<i16>| !samples.is_empty())
+            .collect();
+
+        let mut scheduler = Self {
+            strikes,
+            channels,
+            sample_rate,
+            min_gap: min_gap.min(max_gap),
+            max_gap,
+            intensity_variance: intensity_variance.clamp(0.0, 1.0),
+            current: None,
+            silence_remaining: 0,
+            rng,
+        };
+
+        // Start with a random pause so the first strike doesn't always open the session
+        scheduler.silence_remaining = scheduler.random_gap();
+        scheduler
+    }
+
+    /// Pick a random gap length, in samples
+    fn random_gap(&mut self) -> usize {
+        let min = self.min_gap.as_secs_f32();
+        let max = self.max_gap.as_secs_f32();
+        let gap = min + (max - min) * self.rng.f32();
+
+        let frames = (gap * self.sample_rate as f32) as usize;
+        frames * self.channels as usize
+    }
+
+    /// Pick the next strike and how loud to play it
+    fn start_strike(&mut self) {
+        let index = self.rng.usize(..self.strikes.len());
+        let gain = 1.0 - self.intensity_variance * self.rng.f32();
+        self.current = Some((index, 0, gain));
+    }
+}
+
+impl Iterator for RandomOneShots {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        if self.strikes.is_empty() {
+            return None;
+        }
+
+        if self.current.is_none() {
+            if self.silence_remaining > 0 {
+                self.silence_remaining -= 1;
+                return Some(0);
+            }
+            self.start_strike();
+        }
+
+        let (index, position, gain) = self.current.as_mut()?;
+        let strike = &self.strikes[*index];
+        let sample = (strike[*position] as f32 * *gain) as i16;
+
+        *position += 1;
+        if *position >= strike.len() {
+            self.current = None;
+            self.silence_remaining = self.random_gap();
+        }
+
+        Some(sample)
+    }
+}
+
+impl Source for RandomOneShots {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    /// A mono strike at 1 kHz holding one level for `len` samples
+    fn strike(len: usize, level: i16) -> SamplesBuffer<i16> {
+        SamplesBuffer::new(1, 1000, vec![level; len])
+    }
+
+    /// Seeded scheduler over three 50-sample strikes, with gaps of 100 to 300 samples
+    fn scheduler(intensity_variance: f32, seed: u64) -> RandomOneShots {
+        RandomOneShots::with_rng(
+            vec![strike(50, 10000), strike(50, 20000), strike(50, 30000)],
+            Duration::from_millis(100),
+            Duration::from_millis(300),
+            intensity_variance,
+            fastrand::Rng::with_seed(seed),
+        )
+    }
+
+    /// Split the output into runs of silence and sound, as (silent, samples)
+    fn runs(source: RandomOneShots, samples: usize) -> Vec<(bool, Vec<i16>)> {
+        let mut runs: Vec<(bool, Vec<i16>)> = Vec::new();
+        for sample in source.take(samples) {
+            let silent = sample == 0;
+            match runs.last_mut() {
+                Some((last, run)) if *last == silent => run.push(sample),
+                _ => runs.push((silent, vec![sample])),
+            }
+        }
+        // The last run may have been cut off
+        runs.pop();
+        runs
+    }
+
+    #[test]
+    fn gaps_stay_within_limits_and_are_silent() {
+        for seed in 0..10 {
+            let runs = runs(scheduler(0.5, seed), 20_000);
+            assert!(runs.len() > 40);
+
+            for (silent, run) in &runs {
+                if *silent {
+                    assert!((100..=300).contains(&run.len()), "gap of {}", run.len());
+                } else {
+                    // Whole strikes only, each at a single gain
+                    assert_eq!(run.len(), 50);
+                    assert!(run.iter().all(|&sample| sample == run[0]));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn gaps_vary() {
+        let gaps: Vec<usize> = runs(scheduler(0.0, 1), 20_000)
+            .into_iter()
+            .filter(|(silent, _)| *silent)
+            .map(|(_, run)| run.len())
+            .collect();
+
+        assert!(gaps.iter().any(|&gap| gap < 150));
+        assert!(gaps.iter().any(|&gap| gap > 250));
+    }
+
+    #[test]
+    fn intensity_variance_lowers_gain() {
+        let levels = |variance| -> Vec<i16> {
+            runs(scheduler(variance, 3), 50_000)
+                .into_iter()
+                .filter(|(silent, _)| !*silent)
+                .map(|(_, run)| run[0])
+                .collect()
+        };
+
+        // Without variance every strike plays at its own level
+        assert!(levels(0.0)
+            .iter()
+            .all(|level| [10000, 20000, 30000].contains(level)));
+
+        // With it a strike plays between half and full level, at varying gains
+        let varied = levels(0.5);
+        for level in &varied {
+            assert!(
+                [10000, 20000, 30000]
+                    .iter()
+                    .any(|full| (full / 2..=*full).contains(level)),
+                "level {}",
+                level
+            );
+        }
+        let mut distinct = varied.clone();
+        distinct.sort_unstable();
+        distinct.dedup();
+        assert!(distinct.len() > varied.len() / 2);
+    }
+
+    #[test]
+    fn without_samples_ends_immediately() {
+        let none: Vec<SamplesBuffer<i16>> = Vec::new();
+        let mut source = RandomOneShots::new(
+            none,
+            Duration::from_millis(100),
+            Duration::from_millis(300),
+            0.5,
+        );
+        assert_eq!(source.next(), None);
+
+        // Empty files are dropped, leaving nothing to play
+        let mut source = RandomOneShots::new(
+            vec![strike(0, 0), strike(0, 0)],
+            Duration::from_millis(100),
+            Duration::from_millis(300),
+            0.5,
+        );
+        assert_eq!(source.next(), None);
+    }
+}
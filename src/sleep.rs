@@ -0,0 +1,165 @@
+use std::time::{Duration, Instant};
+
+/// Durations the sleep timer key cycles through, in minutes
+pub const PRESET_MINUTES: &[u64] = &[15, 30, 60, 90];
+
+/// Longest session a timer or rendering can be set to
+pub const MAX_DURATION: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Step used when extending or shortening a running timer
+pub const ADJUST_STEP: Duration = Duration::from_secs(5 * 60);
+
+/// Counts down to the end of a session and fades the volume out over its final minutes
+#[derive(Debug, Clone)]
+pub struct SleepTimer {
+    ends_at: Instant,
+    duration: Duration,
+    fade: Duration,
+}
+
+impl SleepTimer {
+    /// Start a timer that ends after `duration`, fading out over the last `fade`
+    pub fn new(duration: Duration, fade: Duration) -> Self {
+        Self::resume(duration, duration, fade)
+    }
+
+    /// Recreate a timer that has `remaining` time left, e.g. from the status of a daemon.
+    /// Times beyond `MAX_DURATION` are cut down to it.
+    pub fn resume(duration: Duration, remaining: Duration, fade: Duration) -> Self {
+        Self {
+            ends_at: Instant::now() + remaining.min(MAX_DURATION),
+            duration,
+            fade,
+        }
+    }
+
+    /// Total length the timer was set to
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// Time left until the timer ends
+    pub fn remaining(&self) -> Duration {
+        self.ends_at.saturating_duration_since(Instant::now())
+    }
+
+    /// Check if the timer has run out
+    pub fn is_finished(&self) -> bool {
+        self.remaining().is_zero()
+    }
+
+    /// Check if the timer is in its fade-out window
+    pub fn is_fading(&self) -> bool {
+        self.remaining() < self.fade
+    }
+
+    /// Multiplier for the master volume (1.0 until the fade starts, then down to 0.0).
+    /// The curve is squared so the fade sounds even to the ear rather than dropping off late.
+    pub fn volume_factor(&self) -> f32 {
+        if self.fade.is_zero() {
+            return if self.is_finished() { 0.0 } else { 1.0 };
+        }
+
+        let progress = (self.remaining().as_secs_f32() / self.fade.as_secs_f32()).min(1.0);
+        progress * progress
+    }
+}
+
+/// Format a duration as h:mm:ss or mm:ss for display
+pub fn format_remaining(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (hours, minutes, seconds) = (secs / 3600, (secs % 3600) / 60, secs % 60);
+
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{:02}:{:02}", minutes, seconds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+    const FADE: Duration = Duration::from_secs(100);
+
+    /// A timer with `secs` left of an hour, fading out over the last `fade`
+    fn timer(secs: u64, fade: Duration) -> SleepTimer {
+        SleepTimer {
+            ends_at: Instant::now() + Duration::from_secs(secs),
+            duration: HOUR,
+            fade,
+        }
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 0.001,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn full_volume_before_the_fade() {
+        let timer = timer(1000, FADE);
+        assert!(!timer.is_fading());
+        assert!(!timer.is_finished());
+        assert_eq!(timer.volume_factor(), 1.0);
+        assert_eq!(timer.duration(), HOUR);
+    }
+
+    #[test]
+    fn fades_out_over_the_window() {
+        // Squared, so half way through the window plays at a quarter
+        let expected = [(100, 1.0), (75, 0.5625), (50, 0.25), (25, 0.0625), (0, 0.0)];
+        for (secs, factor) in expected {
+            assert_close(timer(secs, FADE).volume_factor(), factor);
+        }
+
+        assert!(timer(99, FADE).is_fading());
+        assert!(!timer(99, FADE).is_finished());
+        assert!(timer(0, FADE).is_fading());
+        assert!(timer(0, FADE).is_finished());
+    }
+
+    #[test]
+    fn cuts_off_without_a_fade() {
+        let running = timer(10, Duration::ZERO);
+        assert!(!running.is_fading());
+        assert_eq!(running.volume_factor(), 1.0);
+
+        let finished = timer(0, Duration::ZERO);
+        assert!(finished.is_finished());
+        assert_eq!(finished.volume_factor(), 0.0);
+    }
+
+    #[test]
+    fn counts_down_remaining_time() {
+        let timer = SleepTimer::new(HOUR, FADE);
+        let remaining = timer.remaining();
+        assert!(remaining <= HOUR && remaining > HOUR - Duration::from_secs(1));
+
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(timer.remaining() < remaining);
+
+        // Resuming from a daemon's status never goes past the longest session
+        let resumed = SleepTimer::resume(HOUR * 48, HOUR * 48, FADE);
+        assert!(resumed.remaining() <= MAX_DURATION);
+        assert!(resumed.remaining() > MAX_DURATION - Duration::from_secs(1));
+    }
+
+    #[test]
+    fn formats_remaining_time() {
+        let format = |secs| format_remaining(Duration::from_secs(secs));
+        assert_eq!(format(0), "00:00");
+        assert_eq!(format(59), "00:59");
+        assert_eq!(format(61), "01:01");
+        assert_eq!(format(3599), "59:59");
+        assert_eq!(format(3600), "1:00:00");
+        assert_eq!(format(24 * 3600 + 62), "24:01:02");
+        assert_eq!(format_remaining(Duration::from_millis(1999)), "00:01");
+    }
+}
@@ -0,0 +1,655 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fmt;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+use crate::channels;
+
+/// Name of the sound pack manifest published next to the sounds archive, and kept in the
+/// folder of every installed pack
+pub const MANIFEST_NAME: &str = "sounds.json";
+
+/// Id of the pack that comes with the player. Its channels keep plain ids like `rain`, the
+/// channels of other packs are prefixed with the pack id, like `ocean:waves`.
+pub const DEFAULT_PACK: &str = "default";
+
+/// Folder inside the sounds directory that other packs are installed into
+const PACKS_DIR: &str = "packs";
+
+/// How deep sound files may be inside a pack, e.g. thunder/strike1.mp3
+const MAX_FILE_DEPTH: usize = 2;
+
+/// Manifest of the sounds this player was released with
+const BUILTIN_MANIFEST: &str = include_str!("../sounds.json");
+
+/// A semantic version like 1.2.3
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl Version {
+    pub const fn new(major: u64, minor: u64, patch: u64) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+}
+
+impl FromStr for Version {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let (version, parts) = parse_parts(text)?;
+        if parts < 3 {
+            anyhow::bail!("Invalid version: {} (expected e.g. 1.2.3)", text);
+        }
+        Ok(version)
+    }
+}
+
+impl TryFrom<String> for Version {
+    type Error = anyhow::Error;
+
+    fn try_from(text: String) -> Result<Self> {
+        text.parse()
+    }
+}
+
+impl From<Version> for String {
+    fn from(version: Version) -> Self {
+        version.to_string()
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Parse a version that may leave out the minor and patch numbers, as in requirements.
+/// Returns the version with the missing numbers as 0 and how many numbers were given.
+fn parse_parts(text: &str) -> Result<(Version, usize)> {
+    let invalid = || format!("Invalid version: {}", text);
+    let trimmed = text.trim();
+    let trimmed = trimmed.strip_prefix('v').unwrap_or(trimmed);
+
+    let numbers = trimmed
+        .split('.')
+        .map(|part| part.parse::<u64>())
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(invalid)?;
+    if numbers.is_empty() || numbers.len() > 3 {
+        anyhow::bail!(invalid());
+    }
+
+    let number = |index: usize| numbers.get(index).copied().unwrap_or(0);
+    Ok((Version::new(number(0), number(1), number(2)), numbers.len()))
+}
+
+/// Version of this player, without pre-release or build suffixes
+pub fn player_version() -> Version {
+    let version = env!("CARGO_PKG_VERSION");
+    let release = version.split(['-', '+']).next().unwrap_or(version);
+    release
+        .parse()
+        .expect("Cargo package version is a semantic version")
+}
+
+/// A range of versions, written like in Cargo: comma separated comparisons such as
+/// `>=1.0.0, <2.0.0`. A bare version like `1.2` allows compatible updates (`^1.2`), `~1.2`
+/// allows patch updates and `*` any version.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct VersionReq {
+    text: String,
+    /// Every version matching all of these is in the range
+    bounds: Vec<(Ordering, bool, Version)>,
+}
+
+impl VersionReq {
+    pub fn matches(&self, version: &Version) -> bool {
+        self.bounds.iter().all(|(ordering, inclusive, bound)| {
+            let actual = version.cmp(bound);
+            actual == *ordering || (*inclusive && actual == Ordering::Equal)
+        })
+    }
+}
+
+impl FromStr for VersionReq {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let invalid = || format!("Invalid version requirement: {}", text);
+        let mut bounds = Vec::new();
+
+        for comparison in text.split(',').map(str::trim) {
+            if comparison == "*" {
+                continue;
+            }
+
+            let (operator, version) = comparison
+                .find(|c: char| c.is_ascii_digit() || c == 'v')
+                .map(|index| comparison.split_at(index))
+                .with_context(invalid)?;
+            let (version, parts) = parse_parts(version)?;
+
+            match operator.trim() {
+                ">=" => bounds.push((Ordering::Greater, true, version)),
+                ">" => bounds.push((Ordering::Greater, false, version)),
+                "<=" => bounds.push((Ordering::Less, true, version)),
+                "<" => bounds.push((Ordering::Less, false, version)),
+                "=" => {
+                    bounds.push((Ordering::Greater, true, version));
+                    let end = next_after(version, parts).with_context(invalid)?;
+                    bounds.push((Ordering::Less, false, end));
+                }
+                "~" => {
+                    bounds.push((Ordering::Greater, true, version));
+                    let end = next_after(version, parts.min(2)).with_context(invalid)?;
+                    bounds.push((Ordering::Less, false, end));
+                }
+                "^" | "" => {
+                    // Everything up to the next change of the first non-zero number
+                    let significant = if version.major > 0 || parts == 1 {
+                        1
+                    } else if version.minor > 0 || parts == 2 {
+                        2
+                    } else {
+                        3
+                    };
+                    bounds.push((Ordering::Greater, true, version));
+                    let end = next_after(version, significant).with_context(invalid)?;
+                    bounds.push((Ordering::Less, false, end));
+                }
+                _ => anyhow::bail!(invalid()),
+            }
+        }
+
+        Ok(Self {
+            text: text.trim().to_string(),
+            bounds,
+        })
+    }
+}
+
+/// Smallest version that differs from `version` in its first `parts` numbers, `None` if there
+/// is no such version
+fn next_after(version: Version, parts: usize) -> Option<Version> {
+    Some(match parts {
+        1 => Version::new(version.major.checked_add(1)?, 0, 0),
+        2 => Version::new(version.major, version.minor.checked_add(1)?, 0),
+        _ => Version::new(version.major, version.minor, version.patch.checked_add(1)?),
+    })
+}
+
+impl TryFrom<String> for VersionReq {
+    type Error = anyhow::Error;
+
+    fn try_from(text: String) -> Result<Self> {
+        text.parse()
+    }
+}
+
+impl From<VersionReq> for String {
+    fn from(req: VersionReq) -> Self {
+        req.text
+    }
+}
+
+impl fmt::Display for VersionReq {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+/// Describes a pack of sounds. The pack has its own version, which only changes when the
+/// sounds do, so player releases that keep the sounds don't ask for a new download.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PackManifest {
+    /// Short identifier, used for the pack's folder, its channel ids and `packs remove`
+    pub id: String,
+    /// Name shown in `packs list`, e.g. "Café"
+    pub name: String,
+    pub version: Version,
+    /// Player versions the sounds work with
+    pub player: VersionReq,
+    /// Sounds in mixer order
+    pub sounds: Vec<PackSound>,
+}
+
+/// A sound of a pack, played on its own mixer channel
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PackSound {
+    /// Identifier within the pack
+    pub id: String,
+    /// Name shown in the mixer
+    pub name: String,
+    /// Sound file, relative to the pack's folder
+    pub file: String,
+    /// Volume of the channel until it's changed (0-100)
+    #[serde(default = "default_volume")]
+    pub volume: u8,
+    /// License the sound is used under
+    pub license: String,
+    /// Who made the sound and where it's from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credit: Option<String>,
+}
+
+fn default_volume() -> u8 {
+    70
+}
+
+impl PackManifest {
+    pub fn parse(contents: &str) -> Result<Self> {
+        let manifest: Self =
+            serde_json::from_str(contents).context("Invalid sound pack manifest")?;
+        manifest.validate()?;
+        Ok(manifest)
+    }
+
+    /// The pack that comes with this player, for sounds installed without a manifest
+    pub fn builtin() -> Self {
+        Self::parse(BUILTIN_MANIFEST).expect("Built-in sound pack manifest is valid")
+    }
+
+    /// Ids and files end up in paths and channel ids, so only allow simple ones. Names are
+    /// only shown, but mustn't mess up the terminal.
+    fn validate(&self) -> Result<()> {
+        if !is_pack_id(&self.id) {
+            anyhow::bail!(
+                "Invalid sound pack id '{}', only lowercase letters, digits and '-' are allowed",
+                self.id
+            );
+        }
+        if !is_display_name(&self.name) {
+            anyhow::bail!("Invalid name for sound pack {}", self.id);
+        }
+        if self.sounds.is_empty() {
+            anyhow::bail!("Sound pack {} has no sounds", self.id);
+        }
+
+        for (index, sound) in self.sounds.iter().enumerate() {
+            if !is_pack_id(&sound.id) {
+                anyhow::bail!("Invalid sound id '{}' in sound pack {}", sound.id, self.id);
+            }
+            if !is_display_name(&sound.name) {
+                anyhow::bail!(
+                    "Invalid name for sound '{}' in sound pack {}",
+                    sound.id,
+                    self.id
+                );
+            }
+            if self.sounds[..index]
+                .iter()
+                .any(|other| other.id == sound.id)
+            {
+                anyhow::bail!(
+                    "Duplicate sound id '{}' in sound pack {}",
+                    sound.id,
+                    self.id
+                );
+            }
+            if !is_pack_file(&sound.file) {
+                anyhow::bail!(
+                    "Invalid sound file '{}' in sound pack {}",
+                    sound.file,
+                    self.id
+                );
+            }
+            if sound.volume > 100 {
+                anyhow::bail!(
+                    "Invalid volume {} for '{}' in sound pack {}, expected 0-100",
+                    sound.volume,
+                    sound.id,
+                    self.id
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn is_default(&self) -> bool {
+        self.id == DEFAULT_PACK
+    }
+
+    /// Folder of the pack, relative to the sounds directory
+    pub fn dir(&self) -> PathBuf {
+        pack_dir(&self.id)
+    }
+
+    /// Channel id of one of the pack's sounds
+    pub fn channel_id(&self, sound: &PackSound) -> String {
+        if self.is_default() {
+            sound.id.clone()
+        } else {
+            format!("{}:{}", self.id, sound.id)
+        }
+    }
+
+    /// Sound files of the pack that are not in `dir`
+    pub fn missing_files(&self, dir: &Path) -> Vec<&str> {
+        self.sounds
+            .iter()
+            .map(|sound| sound.file.as_str())
+            .filter(|file| !dir.join(file).exists())
+            .collect()
+    }
+
+    /// Fail if the sounds don't work with this player version
+    pub fn check_compatible(&self, player: &Version) -> Result<()> {
+        if !self.player.matches(player) {
+            anyhow::bail!(
+                "Sound pack v{} needs relax-player {}, this is v{}",
+                self.version,
+                self.player,
+                player
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Folder of the pack with the given id, relative to the sounds directory. The default pack
+/// lives in the sounds directory itself.
+pub fn pack_dir(id: &str) -> PathBuf {
+    if id == DEFAULT_PACK {
+        PathBuf::new()
+    } else {
+        Path::new(PACKS_DIR).join(id)
+    }
+}
+
+/// Check if an id can be used for a pack or a sound
+pub fn is_pack_id(id: &str) -> bool {
+    !id.is_empty()
+        && !id.starts_with('-')
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-')
+}
+
+/// Check that a name has something to show and no control characters
+fn is_display_name(name: &str) -> bool {
+    !name.trim().is_empty() && !name.chars().any(char::is_control)
+}
+
+/// Check that a sound file stays inside the pack's folder
+fn is_pack_file(file: &str) -> bool {
+    let path = Path::new(file);
+    let components: Vec<_> = path.components().collect();
+
+    !components.is_empty()
+        && components.len() <= MAX_FILE_DEPTH
+        && components
+            .iter()
+            .all(|component| matches!(component, Component::Normal(_)))
+        && channels::is_sound_file(path)
+}
+
+/// Read the manifest of an installed pack
+pub fn read_manifest(path: &Path) -> Result<PackManifest> {
+    let contents =
+        fs::read_to_string(path).context(format!("Failed to read {}", path.display()))?;
+    PackManifest::parse(&contents).context(format!("Failed to read {}", path.display()))
+}
+
+/// The installed default pack, or the one built into the player for sounds installed without
+/// a manifest
+pub fn default_pack(sounds_dir: &Path) -> PackManifest {
+    read_manifest(&sounds_dir.join(MANIFEST_NAME))
+        .ok()
+        .filter(PackManifest::is_default)
+        .unwrap_or_else(PackManifest::builtin)
+}
+
+/// All installed packs, the default pack first and the others by name. Folders without a
+/// valid manifest are skipped.
+pub fn installed_packs(sounds_dir: &Path) -> Result<Vec<PackManifest>> {
+    let mut packs = vec![default_pack(sounds_dir)];
+
+    let packs_dir = sounds_dir.join(PACKS_DIR);
+    if !packs_dir.is_dir() {
+        return Ok(packs);
+    }
+
+    let entries = fs::read_dir(&packs_dir).context(format!(
+        "Failed to read sound packs directory: {}",
+        packs_dir.display()
+    ))?;
+    let mut others: Vec<PackManifest> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let manifest = read_manifest(&entry.path().join(MANIFEST_NAME)).ok()?;
+            // The folder name is what `packs remove` goes by
+            let folder = entry.file_name();
+            (!manifest.is_default() && folder.to_str() == Some(manifest.id.as_str()))
+                .then_some(manifest)
+        })
+        .collect();
+    others.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.id.cmp(&b.id)));
+
+    packs.extend(others);
+    Ok(packs)
+}
+
+/// How the installed sounds compare to the pack available for this player
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UpdateCheck {
+    UpToDate,
+    /// The available pack is newer, or the installed version is unknown
+    Update,
+    /// The installed pack is newer than the available one
+    Downgrade(Version),
+}
+
+/// Compare the installed pack version (as stored in the config) with the available pack
+pub fn check_update(installed: Option<&str>, available: &PackManifest) -> UpdateCheck {
+    let Some(installed) = installed.and_then(|version| version.parse::<Version>().ok()) else {
+        return UpdateCheck::Update;
+    };
+
+    match installed.cmp(&available.version) {
+        Ordering::Less => UpdateCheck::Update,
+        Ordering::Equal => UpdateCheck::UpToDate,
+        Ordering::Greater => UpdateCheck::Downgrade(installed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn req(text: &str) -> VersionReq {
+        text.parse().unwrap()
+    }
+
+    fn version(text: &str) -> Version {
+        text.parse().unwrap()
+    }
+
+    fn manifest(id: &str, name: &str, sounds: &str) -> String {
+        format!(
+            r#"{{"id": "{}", "name": "{}", "version": "1.0.0", "player": "*", "sounds": [{}]}}"#,
+            id, name, sounds
+        )
+    }
+
+    fn write_pack(dir: &Path, id: &str, name: &str, sounds: &str) {
+        fs::create_dir_all(dir).unwrap();
+        fs::write(dir.join(MANIFEST_NAME), manifest(id, name, sounds)).unwrap();
+    }
+
+    const WAVES: &str =
+        r#"{"id": "waves", "name": "Waves", "file": "waves.ogg", "license": "CC0"}"#;
+
+    #[test]
+    fn orders_versions_numerically() {
+        assert!(version("1.10.0") > version("1.9.3"));
+        assert!(version("2.0.0") > version("1.99.99"));
+        assert_eq!(version("v1.2.3"), Version::new(1, 2, 3));
+        assert!("1.2".parse::<Version>().is_err());
+        assert!("1.2.x".parse::<Version>().is_err());
+        assert!(player_version() >= Version::new(1, 0, 0));
+    }
+
+    #[test]
+    fn matches_version_ranges() {
+        let range = req(">=1.1.0, <2.0.0");
+        assert!(range.matches(&version("1.1.0")));
+        assert!(range.matches(&version("1.9.9")));
+        assert!(!range.matches(&version("1.0.9")));
+        assert!(!range.matches(&version("2.0.0")));
+
+        assert!(req("1.2").matches(&version("1.9.0")));
+        assert!(!req("1.2").matches(&version("1.1.9")));
+        assert!(!req("^0.2.1").matches(&version("0.3.0")));
+        assert!(req("~1.2").matches(&version("1.2.7")));
+        assert!(!req("~1.2").matches(&version("1.3.0")));
+        assert!(req("=1.2.3").matches(&version("1.2.3")));
+        assert!(!req("=1.2.3").matches(&version("1.2.4")));
+        assert!(req("*").matches(&version("0.0.1")));
+
+        assert!("=> 1.0".parse::<VersionReq>().is_err());
+        assert!(">=".parse::<VersionReq>().is_err());
+        assert!("^18446744073709551615".parse::<VersionReq>().is_err());
+        assert!("~1.18446744073709551615".parse::<VersionReq>().is_err());
+        assert!("=1.2.18446744073709551615".parse::<VersionReq>().is_err());
+    }
+
+    #[test]
+    fn parses_manifest() {
+        let manifest = PackManifest::parse(
+            r#"{"id": "default", "name": "Relax Player", "version": "1.2.0", "player": ">=1.1.0, <2.0.0",
+                "sounds": [{"id": "rain", "name": "Rain", "file": "rain.mp3", "volume": 50,
+                            "license": "CC0", "credit": "Someone"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(manifest.version, Version::new(1, 2, 0));
+        assert_eq!(manifest.sounds[0].volume, 50);
+        assert_eq!(manifest.sounds[0].credit.as_deref(), Some("Someone"));
+
+        manifest.check_compatible(&version("1.4.2")).unwrap();
+        let error = manifest.check_compatible(&version("2.0.0")).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("needs relax-player >=1.1.0, <2.0.0"));
+
+        assert!(PackManifest::parse(r#"{"version": "1.2", "player": "*"}"#).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_manifests() {
+        assert!(PackManifest::parse(&manifest("ocean", "Ocean", WAVES)).is_ok());
+        assert!(PackManifest::parse(&manifest("Ocean Sounds", "Ocean", WAVES)).is_err());
+        assert!(PackManifest::parse(&manifest("ocean", " ", WAVES)).is_err());
+        assert!(PackManifest::parse(&manifest("ocean", "Ocean\\u001b[2J", WAVES)).is_err());
+        assert!(PackManifest::parse(&manifest("ocean", "Ocean", "")).is_err());
+        assert!(
+            PackManifest::parse(&manifest("ocean", "Ocean", &[WAVES, WAVES].join(","))).is_err()
+        );
+
+        for sound in [
+            r#"{"id": "a", "name": "A", "file": "../a.ogg", "license": "CC0"}"#,
+            r#"{"id": "a", "name": "A", "file": "/tmp/a.ogg", "license": "CC0"}"#,
+            r#"{"id": "a", "name": "A", "file": "a/b/c.ogg", "license": "CC0"}"#,
+            r#"{"id": "a", "name": "A", "file": "a.txt", "license": "CC0"}"#,
+            r#"{"id": "a:b", "name": "A", "file": "a.ogg", "license": "CC0"}"#,
+            r#"{"id": "a", "name": "A", "file": "a.ogg", "volume": 101, "license": "CC0"}"#,
+        ] {
+            assert!(
+                PackManifest::parse(&manifest("ocean", "Ocean", sound)).is_err(),
+                "{}",
+                sound
+            );
+        }
+    }
+
+    #[test]
+    fn names_channels_by_pack() {
+        let cafe = PackManifest::parse(&manifest("cafe", "Café", WAVES)).unwrap();
+        assert_eq!(cafe.name, "Café");
+        assert_eq!(cafe.channel_id(&cafe.sounds[0]), "cafe:waves");
+        assert_eq!(cafe.dir(), Path::new("packs/cafe"));
+
+        let builtin = PackManifest::builtin();
+        assert!(builtin.is_default());
+        assert_eq!(builtin.channel_id(&builtin.sounds[0]), "rain");
+        assert_eq!(builtin.dir(), PathBuf::new());
+    }
+
+    #[test]
+    fn lists_installed_packs() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(
+            installed_packs(dir.path()).unwrap(),
+            vec![PackManifest::builtin()]
+        );
+
+        let packs = dir.path().join(PACKS_DIR);
+        write_pack(&packs.join("ocean"), "ocean", "Ocean", WAVES);
+        write_pack(&packs.join("birds"), "birds", "Birds", WAVES);
+        // Folder doesn't match the id, or no manifest at all
+        write_pack(&packs.join("other"), "forest", "Forest", WAVES);
+        fs::create_dir_all(packs.join("empty")).unwrap();
+
+        let ids: Vec<_> = installed_packs(dir.path())
+            .unwrap()
+            .into_iter()
+            .map(|pack| pack.id)
+            .collect();
+        assert_eq!(ids, ["default", "birds", "ocean"]);
+    }
+
+    #[test]
+    fn finds_missing_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let pack = PackManifest::builtin();
+        assert_eq!(pack.missing_files(dir.path()).len(), pack.sounds.len());
+
+        for sound in &pack.sounds {
+            fs::write(dir.path().join(&sound.file), b"").unwrap();
+        }
+        assert!(pack.missing_files(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn published_manifest_fits_this_player() {
+        let manifest = PackManifest::parse(include_str!("../sounds.json")).unwrap();
+        manifest.check_compatible(&player_version()).unwrap();
+    }
+
+    #[test]
+    fn detects_updates_and_downgrades() {
+        let available = PackManifest {
+            version: version("1.2.0"),
+            ..PackManifest::builtin()
+        };
+
+        assert_eq!(
+            check_update(Some("1.2.0"), &available),
+            UpdateCheck::UpToDate
+        );
+        assert_eq!(check_update(Some("1.1.5"), &available), UpdateCheck::Update);
+        assert_eq!(check_update(None, &available), UpdateCheck::Update);
+        assert_eq!(
+            check_update(Some("garbage"), &available),
+            UpdateCheck::Update
+        );
+        assert_eq!(
+            check_update(Some("1.10.0"), &available),
+            UpdateCheck::Downgrade(version("1.10.0"))
+        );
+    }
+}
@@ -1,4 +1,7 @@
 use crate::app::{App, Channel};
+use crate::filter;
+use crate::pan;
+use crate::sleep;
 use ratatui::{
     buffer::Buffer,
     layout::{Alignment, Constraint, Direction, Layout, Rect},
@@ -19,7 +22,7 @@ pub fn render(f: &mut Frame, app: &App) {
         .split(f.area());
 
     render_channels(f, app, chunks[0]);
-    render_help(f, chunks[1]);
+    render_help(f, app, chunks[1]);
 }
 
 /// Render the channel volume bars (alsamixer style)
@@ -31,20 +34,19 @@ fn render_channels(f: &mut Frame, app: &App, area: Rect) {
     let inner = block.inner(area);
     f.render_widget(block, area);
 
-    // Split into 4 columns (one for each channel)
+    // Split into one equally sized column for each channel
+    let channels = app.channels();
     let columns = Layout::default()
         .direction(Direction::Horizontal)
-        .constraints([
-            Constraint::Percentage(25),
-            Constraint::Percentage(25),
-            Constraint::Percentage(25),
-            Constraint::Percentage(25),
+        .constraints(vec![
+            Constraint::Ratio(1, channels.len() as u32);
+            channels.len()
         ])
         .split(inner);
 
     // Render each channel
-    for (i, channel) in Channel::all().iter().enumerate() {
-        render_channel_bar(f, app, *channel, columns[i]);
+    for (channel, column) in channels.into_iter().zip(columns.iter()) {
+        render_channel_bar(f, app, channel, *column);
     }
 }
 
@@ -54,13 +56,15 @@ fn render_channel_bar(f: &mut Frame, app: &App, channel: Channel, area: Rect) {
     let volume = app.get_volume(channel);
     let is_muted = app.is_muted(channel);
 
-    // Split area into: title, bar, volume text
+    // Split area into: title, bar, volume text, pan, filters
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
             Constraint::Length(1), // Title
             Constraint::Min(0),    // Bar
             Constraint::Length(1), // Volume %
+            Constraint::Length(1), // Pan
+            Constraint::Length(1), // Tone filters
         ])
         .split(area);
 
@@ -73,7 +77,7 @@ fn render_channel_bar(f: &mut Frame, app: &App, channel: Channel, area: Rect) {
         Style::default()
     };
 
-    let title = Paragraph::new(channel.name())
+    let title = Paragraph::new(app.channel_name(channel))
         .style(title_style)
         .alignment(Alignment::Center);
     f.render_widget(title, chunks[0]);
@@ -89,15 +93,36 @@ fn render_channel_bar(f: &mut Frame, app: &App, channel: Channel, area: Rect) {
     // Render volume percentage and mute indicator
     let mute_indicator = if is_muted { " 🔇" } else { "" };
     let vol_text = format!("[{}%]{}", volume, mute_indicator);
-    let vol_paragraph =
-        Paragraph::new(vol_text)
-            .alignment(Alignment::Center)
-            .style(if is_selected {
-                Style::default().fg(Color::Yellow)
-            } else {
-                Style::default()
-            });
+    let text_style = if is_selected {
+        Style::default().fg(Color::Yellow)
+    } else {
+        Style::default()
+    };
+    let vol_paragraph = Paragraph::new(vol_text)
+        .alignment(Alignment::Center)
+        .style(text_style);
     f.render_widget(vol_paragraph, chunks[2]);
+
+    // Render stereo position and tone filters, the master volume has neither
+    if let Channel::Sound(_) = channel {
+        let pan_text = format!("◀ {} ▶", pan::format_pan(app.get_pan(channel)));
+        let pan_paragraph = Paragraph::new(pan_text)
+            .alignment(Alignment::Center)
+            .style(text_style);
+        f.render_widget(pan_paragraph, chunks[3]);
+
+        let (low_pass, high_pass) = app.get_filters(channel);
+        let filters: Vec<String> = [("LP", low_pass), ("HP", high_pass)]
+            .into_iter()
+            .filter_map(|(label, hz)| {
+                hz.map(|hz| format!("{} {}", label, filter::format_cutoff(hz)))
+            })
+            .collect();
+        let filter_paragraph = Paragraph::new(filters.join(" "))
+            .alignment(Alignment::Center)
+            .style(text_style);
+        f.render_widget(filter_paragraph, chunks[4]);
+    }
 }
 
 /// Custom widget for rendering a vertical volume bar
@@ -159,23 +184,83 @@ impl Widget for VolumeBar {
     }
 }
 
-/// Render help text
-fn render_help(f: &mut Frame, area: Rect) {
+/// Render help text, with playback and sleep timer status in the border
+fn render_help(f: &mut Frame, app: &App, area: Rect) {
     let help_lines = vec![Line::from(vec![
         Span::raw("←/→ "),
         Span::styled("h/l", Style::default().add_modifier(Modifier::BOLD)),
         Span::raw(": Select  ↑/↓ "),
         Span::styled("j/k", Style::default().add_modifier(Modifier::BOLD)),
         Span::raw(": Volume  "),
+        Span::styled(",/.", Style::default().add_modifier(Modifier::BOLD)),
+        Span::raw(": Pan  "),
+        Span::styled("[]", Style::default().add_modifier(Modifier::BOLD)),
+        Span::raw("/"),
+        Span::styled("{}", Style::default().add_modifier(Modifier::BOLD)),
+        Span::raw(": Filters  "),
         Span::styled("m", Style::default().add_modifier(Modifier::BOLD)),
         Span::raw(": Mute  "),
+        Span::styled("space", Style::default().add_modifier(Modifier::BOLD)),
+        Span::raw(": Pause  "),
+        Span::styled("t", Style::default().add_modifier(Modifier::BOLD)),
+        Span::raw("/"),
+        Span::styled("+-", Style::default().add_modifier(Modifier::BOLD)),
+        Span::raw(": Sleep  "),
+        Span::styled("p", Style::default().add_modifier(Modifier::BOLD)),
+        Span::raw("/"),
+        Span::styled("s", Style::default().add_modifier(Modifier::BOLD)),
+        Span::raw("/"),
+        Span::styled("x", Style::default().add_modifier(Modifier::BOLD)),
+        Span::raw(": Preset  "),
+        Span::styled("o", Style::default().add_modifier(Modifier::BOLD)),
+        Span::raw(": Output  "),
         Span::styled("q", Style::default().add_modifier(Modifier::BOLD)),
         Span::raw(": Quit"),
     ])];
 
+    let mut block = Block::default().borders(Borders::ALL);
+    if let Some(status) = status_text(app) {
+        block = block.title(
+            Line::from(Span::styled(status, Style::default().fg(Color::Yellow))).right_aligned(),
+        );
+    }
+
     let help = Paragraph::new(help_lines)
-        .block(Block::default().borders(Borders::ALL))
+        .block(block)
         .alignment(Alignment::Center);
 
     f.render_widget(help, area);
 }
+
+/// Status shown above the help text: output device, current preset, pause state and sleep
+/// timer countdown
+fn status_text(app: &App) -> Option<String> {
+    let mut parts = Vec::new();
+
+    if let Some(device) = &app.device {
+        parts.push(format!("Output: {}", device));
+    }
+
+    if let Some(preset) = &app.preset {
+        parts.push(format!("Preset: {}", preset));
+    }
+
+    if app.paused {
+        parts.push("Paused".to_string());
+    }
+
+    if let Some(timer) = &app.sleep_timer {
+        let fading = if timer.is_fading() { " (fading)" } else { "" };
+        parts.push(format!(
+            "Sleep in {}{}",
+            sleep::format_remaining(timer.remaining()),
+            fading
+        ));
+    }
+
+    if parts.is_empty() {
+        None
+    } else {
+        Some(format!(" {} ", parts.join(" | ")))
+    }
+}